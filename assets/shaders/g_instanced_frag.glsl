@@ -0,0 +1,18 @@
+#version 330 core
+
+#include "shaders/include/gbuffer_out.glsl"
+#include "shaders/include/frag_in.glsl"
+
+#include "shaders/include/material_constants.glsl"
+
+in vec4 vInstanceColor;
+
+void main() {
+
+    Position = FragPos;
+    Normal = vec4(normalize(vNormal), 1.0);
+    Albedo = vec4(albedo * vInstanceColor.rgb, 1.0);
+    Metallic = metallic;
+    Roughness = roughness;
+    AO = ao;
+}
@@ -0,0 +1,20 @@
+#version 330 core
+
+#include "shaders/include/vert_common.glsl"
+
+// per instance, see InstancedRenderObject
+layout (location = 8) in mat4 aInstanceModel;
+layout (location = 12) in vec4 aInstanceColor;
+
+out vec4 vInstanceColor;
+
+void main() {
+    mat4 instanceModel = model * aInstanceModel;
+
+    gl_Position = proj * view * instanceModel * vec4(aPosition, 1.0);
+    FragPos = vec3(instanceModel * vec4(aPosition, 1.0));
+    // no non-uniform scale on instances, so the upper 3x3 is good enough for normals
+    vNormal = normalize(mat3(model_rot) * mat3(aInstanceModel) * aNormal);
+    vST = aST;
+    vInstanceColor = aInstanceColor;
+}
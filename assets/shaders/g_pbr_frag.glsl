@@ -0,0 +1,24 @@
+#version 330 core
+
+#include "shaders/include/gbuffer_out.glsl"
+#include "shaders/include/frag_in.glsl"
+
+// glTF channel packing: occlusion in r, roughness in g, metallic in b
+uniform sampler2D albedo;
+uniform sampler2D metallic;
+uniform sampler2D roughness;
+uniform sampler2D ao;
+// multiply the texture channels
+uniform vec3 albedo_factor;
+uniform float metallic_factor;
+uniform float roughness_factor;
+
+void main() {
+
+    Position = FragPos;
+    Normal = vec4(normalize(vNormal), 1.0);
+    Albedo = texture(albedo, vST) * vec4(albedo_factor, 1.0);
+    Metallic = texture(metallic, vST).b * metallic_factor;
+    Roughness = texture(roughness, vST).g * roughness_factor;
+    AO = texture(ao, vST).r;
+}
\ No newline at end of file
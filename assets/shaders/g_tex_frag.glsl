@@ -1,17 +1,10 @@
 #version 330 core
 
-layout (location = 0) out vec3 Position;
-layout (location = 1) out vec4 Normal;
-layout (location = 2) out vec4 Albedo;
-layout (location = 3) out float Metallic;
-layout (location = 4) out float Roughness;
-layout (location = 5) out float AO;
-
-in vec3 FragPos;
-in vec3 vNormal;
-in vec2 vST;
+#include "shaders/include/gbuffer_out.glsl"
+#include "shaders/include/frag_in.glsl"
 
 uniform sampler2D albedo;
+uniform vec3 albedo_factor;
 uniform float metallic;
 uniform float roughness;
 uniform float ao;
@@ -20,7 +13,7 @@ void main() {
 
     Position = FragPos;
     Normal = vec4(normalize(vNormal), 1.0);
-    Albedo = texture(albedo, vST);
+    Albedo = texture(albedo, vST) * vec4(albedo_factor, 1.0);
     Metallic = metallic;
     Roughness = roughness;
     AO = ao;
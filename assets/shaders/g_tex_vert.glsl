@@ -1,23 +1,6 @@
 #version 330 core
 
-layout (location = 0) in vec3 aPosition;
-layout (location = 1) in vec3 aNormal;
-layout (location = 2) in vec2 aST;
-
-out vec3 FragPos;
-out vec3 vNormal;
-out vec2 vST;
-
-uniform vec2 iResolution;
-uniform vec4 iMouse;
-uniform float iTimeDelta;
-
-
-uniform mat4 model;
-uniform mat4 proj;
-uniform mat4 view;
-
-uniform mat4 model_rot;
+#include "shaders/include/vert_common.glsl"
 
 
 void main() {
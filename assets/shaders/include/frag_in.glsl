@@ -0,0 +1,3 @@
+in vec3 FragPos;
+in vec3 vNormal;
+in vec2 vST;
@@ -0,0 +1,7 @@
+// g-buffer targets, attachment order matches framebuffer::GBuffer
+layout (location = 0) out vec3 Position;
+layout (location = 1) out vec4 Normal;
+layout (location = 2) out vec4 Albedo;
+layout (location = 3) out float Metallic;
+layout (location = 4) out float Roughness;
+layout (location = 5) out float AO;
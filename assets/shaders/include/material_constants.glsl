@@ -0,0 +1,4 @@
+uniform vec3 albedo;
+uniform float metallic;
+uniform float roughness;
+uniform float ao;
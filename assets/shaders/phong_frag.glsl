@@ -1,8 +1,6 @@
 #version 330 core
 
-in vec3 FragPos;
-in vec3 vNormal;
-in vec2 vST;
+#include "shaders/include/frag_in.glsl"
 
 out vec4 Color;
 
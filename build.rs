@@ -3,12 +3,17 @@ Derived from
 https://github.com/Nercury/rust-and-opengl-lessons
 */
 extern crate walkdir;
+extern crate flate2;
 
 use std::env;
-use std::fs::{self, DirBuilder};
+use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+#[path = "src/pack.rs"]
+#[allow(dead_code)]
+mod pack;
+
 fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
@@ -19,9 +24,10 @@ fn main() {
         .expect("failed to find target dir")
         .join(env::var("PROFILE").unwrap());
 
-    copy(
+    println!("cargo:rerun-if-changed=assets");
+    pack_assets(
         &manifest_dir.join("assets"),
-        &executable_path.join("assets"),
+        &executable_path.join("assets.pack"),
     );
 }
 
@@ -42,22 +48,32 @@ fn locate_target_dir_from_output_dir(mut target_dir_search: &Path) -> Option<&Pa
     None
 }
 
-fn copy(from: &Path, to: &Path) {
+// everything under assets/ goes into one archive, resource names use '/' on every platform
+fn pack_assets(from: &Path, to: &Path) {
     let from_path: PathBuf = from.into();
-    let to_path: PathBuf = to.into();
-    for entry in WalkDir::new(from_path.clone()) {
+    let mut writer = pack::PackWriter::new();
+    for entry in WalkDir::new(from_path.clone()).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
         let entry = entry.unwrap();
-
+        if !entry.file_type().is_file() {
+            continue;
+        }
         if let Ok(rel_path) = entry.path().strip_prefix(&from_path) {
-            let target_path = to_path.join(rel_path);
-
-            if entry.file_type().is_dir() {
-                DirBuilder::new()
-                    .recursive(true)
-                    .create(target_path).expect("failed to create target dir");
-            } else {
-                fs::copy(entry.path(), &target_path).expect("failed to copy");
-            }
+            let name = rel_path.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<String>>()
+                .join("/");
+            let bytes = fs::read(entry.path()).expect("failed to read asset");
+            writer.add(&name, bytes, compression_for(&name));
         }
     }
-}
\ No newline at end of file
+    writer.write_to(to).expect("failed to write asset pack");
+}
+
+// already compressed formats are stored as is
+fn compression_for(name: &str) -> pack::Compression {
+    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "glb" => pack::Compression::None,
+        _ => pack::Compression::Deflate,
+    }
+}
@@ -0,0 +1,147 @@
+/*
+background asset loading
+worker threads read and decode meshes and images into GL-free data,
+the GL thread uploads whatever finished within a per-frame time budget
+until then the resource keys point at placeholders, so materials and objects can be set up right away
+*/
+
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::geo::sphere::gen_sphere_mesh;
+use crate::load_assets::{build_mesh_data, build_mesh_lods, build_image_data, finish_texture, Error};
+use crate::loader::FilePath;
+use crate::manifest::{MeshDesc, TextureDesc};
+use crate::mesh::{Mesh, MeshData, LodData};
+use crate::resources::{Resources, Handle};
+use crate::texture::{Texture, ImageData, SamplerDesc};
+
+const MAX_WORKERS: usize = 4;
+
+enum Job {
+    Mesh(Handle<Mesh>, MeshDesc),
+    Texture(Handle<Texture>, TextureDesc),
+}
+
+enum Loaded {
+    Mesh(Handle<Mesh>, MeshDesc, MeshData, Vec<LodData>),
+    Texture(Handle<Texture>, TextureDesc, ImageData),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub uploaded: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    // 0..1, failed assets count as done
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1f32;
+        }
+        (self.uploaded + self.failed) as f32 / self.total as f32
+    }
+    pub fn is_done(&self) -> bool {
+        self.uploaded + self.failed >= self.total
+    }
+}
+
+// workers block on the job channel and exit once the loader is dropped
+pub struct AsyncLoader {
+    jobs: mpsc::Sender<Job>,
+    results: mpsc::Receiver<Result<Loaded, Error>>,
+    progress: LoadProgress,
+}
+
+impl AsyncLoader {
+    pub fn new(resource_path: Arc<FilePath>) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let worker_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(MAX_WORKERS);
+        for _ in 0..worker_count {
+            let resource_path = resource_path.clone();
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::spawn(move || {
+                loop {
+                    // the lock is released before the job runs
+                    let job = job_receiver.lock().unwrap().recv();
+                    let result = match job {
+                        Ok(Job::Mesh(key, desc)) => build_mesh_data(&resource_path, &desc)
+                            .and_then(|data| {
+                                let lods = build_mesh_lods(&data, &desc)?;
+                                Ok(Loaded::Mesh(key, desc, data, lods))
+                            }),
+                        Ok(Job::Texture(key, desc)) => build_image_data(&resource_path, &desc)
+                            .map(|data| Loaded::Texture(key, desc, data)),
+                        Err(_) => break,
+                    };
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        AsyncLoader {
+            jobs,
+            results,
+            progress: LoadProgress { total: 0, uploaded: 0, failed: 0 },
+        }
+    }
+    // registers a placeholder under the mesh's name, the real mesh replaces it behind the same key
+    pub fn queue_mesh(&mut self, desc: &MeshDesc, res: &mut Resources) -> Result<Handle<Mesh>, Error> {
+        let key = res.add_mesh_by_name(&desc.name, gen_sphere_mesh(8, 8))
+            .map_err(|error| Error::Resources { asset: desc.name.clone(), error })?;
+        self.push_job(Job::Mesh(key, desc.clone()));
+        Ok(key)
+    }
+    pub fn queue_texture(&mut self, desc: &TextureDesc, res: &mut Resources) -> Result<Handle<Texture>, Error> {
+        let key = res.add_texture_by_name(&desc.name, placeholder_texture())
+            .map_err(|error| Error::Resources { asset: desc.name.clone(), error })?;
+        self.push_job(Job::Texture(key, desc.clone()));
+        Ok(key)
+    }
+    fn push_job(&mut self, job: Job) {
+        self.jobs.send(job).unwrap();
+        self.progress.total += 1;
+    }
+    // GL thread, call once a frame
+    // uploads finished assets until the budget is spent, always at least one so loading can't stall
+    pub fn upload(&mut self, res: &mut Resources, budget: Duration) {
+        let start = Instant::now();
+        while let Ok(result) = self.results.try_recv() {
+            let uploaded = result.and_then(|loaded| match loaded {
+                Loaded::Mesh(key, desc, data, lods) => res.replace_mesh(key, Mesh::new(data).with_lods(lods))
+                    .map_err(|error| Error::Resources { asset: desc.name.clone(), error }),
+                Loaded::Texture(key, desc, data) => res.replace_texture(key, finish_texture(data, &desc))
+                    .map_err(|error| Error::Resources { asset: desc.name.clone(), error }),
+            });
+            match uploaded {
+                Ok(_) => self.progress.uploaded += 1,
+                // the placeholder stays
+                Err(error) => {
+                    println!("Asset error: {}", error);
+                    self.progress.failed += 1;
+                }
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+}
+
+// flat grey until the real texture is in
+fn placeholder_texture() -> Texture {
+    let mut texture = Texture::create_with_data(gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, (1, 1), vec![128, 128, 128, 255]);
+    texture.set_sampler(SamplerDesc::nearest_clamp());
+    texture
+}
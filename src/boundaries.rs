@@ -34,7 +34,7 @@ pub enum IntersectionResult {
     Displacement(Vector3f),
 }
 
-use crate::math::Vector3f;
+use crate::math::{Vector3f, Mat4f};
 pub fn AABB_AABB_intersection(cyl: &Cylinder, cpos: &Vector3f, aabb: &AABB, apos: &Vector3f) -> IntersectionResult {
     let vdiff = Vector3f::sub(cpos, apos);
 
@@ -43,4 +43,110 @@ pub fn AABB_AABB_intersection(cyl: &Cylinder, cpos: &Vector3f, aabb: &AABB, apos
     } else {
         return IntersectionResult::Displacement(vdiff);
     }
-}
\ No newline at end of file
+}
+
+fn transform_point(m: &Mat4f, p: &Vector3f) -> Vector3f {
+    Vector3f::new(
+        m.get(0, 0) * p.x + m.get(0, 1) * p.y + m.get(0, 2) * p.z + m.get(0, 3),
+        m.get(1, 0) * p.x + m.get(1, 1) * p.y + m.get(1, 2) * p.z + m.get(1, 3),
+        m.get(2, 0) * p.x + m.get(2, 1) * p.y + m.get(2, 2) * p.z + m.get(2, 3),
+    )
+}
+
+// axis aligned, min is above max while empty
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Vector3f,
+    pub max: Vector3f,
+}
+
+impl BoundingBox {
+    pub fn empty() -> Self {
+        BoundingBox {
+            min: Vector3f::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vector3f::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    pub fn add_point(&mut self, p: &Vector3f) {
+        self.min = Vector3f::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+        self.max = Vector3f::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+    }
+    pub fn center(&self) -> Vector3f {
+        Vector3f::new((self.min.x + self.max.x) * 0.5, (self.min.y + self.max.y) * 0.5, (self.min.z + self.max.z) * 0.5)
+    }
+    // half the size on every axis
+    pub fn extents(&self) -> Vector3f {
+        Vector3f::new((self.max.x - self.min.x) * 0.5, (self.max.y - self.min.y) * 0.5, (self.max.z - self.min.z) * 0.5)
+    }
+    pub fn contains(&self, p: &Vector3f) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x
+            && p.y >= self.min.y && p.y <= self.max.y
+            && p.z >= self.min.z && p.z <= self.max.z
+    }
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x
+            && self.min.y <= other.max.y && self.max.y >= other.min.y
+            && self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+    // the box around this box moved by m, rotations make it grow to fit the turned corners
+    pub fn transformed(&self, m: &Mat4f) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = transform_point(m, &self.center());
+        let e = self.extents();
+        let reach = |row: usize| m.get(row, 0).abs() * e.x + m.get(row, 1).abs() * e.y + m.get(row, 2).abs() * e.z;
+        let reach = Vector3f::new(reach(0), reach(1), reach(2));
+        BoundingBox {
+            min: Vector3f::sub(&center, &reach),
+            max: Vector3f::add(&center, &reach),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vector3f,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vector3f, radius: f32) -> Self {
+        BoundingSphere {
+            center,
+            radius,
+        }
+    }
+    // the radius grows with the largest scale, so non-uniform scaling stays inside
+    pub fn transformed(&self, m: &Mat4f) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|column| Vector3f::new(m.get(0, column), m.get(1, column), m.get(2, column)).mag())
+            .fold(0f32, f32::max);
+        BoundingSphere {
+            center: transform_point(m, &self.center),
+            radius: self.radius * scale,
+        }
+    }
+    pub fn intersects(&self, other: &BoundingSphere) -> bool {
+        let reach = self.radius + other.radius;
+        Vector3f::sub(&self.center, &other.center).mag_squared() <= reach * reach
+    }
+    // how far along the unit direction the ray enters the sphere, 0 when it starts inside, None when it misses or the sphere is behind
+    pub fn ray_intersection(&self, origin: &Vector3f, direction: &Vector3f) -> Option<f32> {
+        let diff = Vector3f::sub(origin, &self.center);
+        let p = Vector3f::dot(direction, &diff);
+        let q = Vector3f::dot(&diff, &diff) - self.radius * self.radius;
+        let disc = p * p - q;
+        if disc < 0f32 {
+            return None;
+        }
+        let far = -p + disc.sqrt();
+        if far < 0f32 {
+            return None;
+        }
+        Some((-p - disc.sqrt()).max(0f32))
+    }
+}
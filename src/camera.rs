@@ -1,7 +1,7 @@
 /*
     time to make a little flying camera
 */
-use crate::math::{Mat4f};
+use crate::math::{Mat4f, Vector3f};
 use crate::transform::{Transform};
 
 pub enum CameraType {
@@ -42,6 +42,20 @@ impl Camera {
     pub fn view_mat(&self) -> Mat4f {
         self.transform.inverse_mat()
     }
+    // fraction of the screen's height a sphere covers, 1 and up when it fills the screen
+    pub fn screen_size(&self, center: &Vector3f, radius: f32) -> f32 {
+        let proj = self.proj_mat();
+        match &self.camera_type {
+            CameraType::PerspectiveCamera{ .. } => {
+                let distance = Vector3f::sub(center, &self.transform.translation).mag();
+                if distance <= radius {
+                    return f32::INFINITY;
+                }
+                radius * proj.get(1, 1) / distance
+            },
+            CameraType::OrthographicCamera{ .. } => radius * proj.get(1, 1),
+        }
+    }
     pub fn view_rot(&self) -> Mat4f {
         let x_rot = Mat4f::rotation_x(-self.transform.rotation.x);
         let y_rot = Mat4f::rotation_y(-self.transform.rotation.y);
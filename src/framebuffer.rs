@@ -5,7 +5,7 @@
 
 
 use std::collections::HashMap;
-use crate::texture::{Texture};
+use crate::texture::{Texture, SamplerDesc};
 
 pub enum Error {
 
@@ -53,7 +53,7 @@ impl Framebuffer {
             tex.bind();
             tex.load_memory();
 
-            tex.set_parameters(vec![(gl::TEXTURE_MIN_FILTER, gl::LINEAR), (gl::TEXTURE_MAG_FILTER, gl::LINEAR), (gl::TEXTURE_WRAP_S, gl::REPEAT), (gl::TEXTURE_WRAP_T, gl::REPEAT)]);
+            tex.set_sampler(SamplerDesc::linear_repeat());
             tex.unbind();
 
             unsafe {
@@ -132,4 +132,15 @@ impl GBuffer {
         self.fb.get_attachment(gl::DEPTH_ATTACHMENT).unwrap()
 
     }
+    pub fn attachments(&self) -> Vec<(&'static str, &Texture)> {
+        vec![
+            ("position", self.get_position()),
+            ("normal", self.get_normal()),
+            ("albedo", self.get_albedo()),
+            ("metallic", self.get_metallic()),
+            ("roughness", self.get_roughness()),
+            ("ao", self.get_ao()),
+            ("depth", self.get_depth()),
+        ]
+    }
 }
@@ -6,12 +6,13 @@ use sdl2::{
 };
 use std::{
     time::Duration,
+    path::Path,
 };
 
 use crate::{
     timer::Timer,
     input::InputState,
-    render_object::{TerrainChunkObject, RenderObject},
+    render_object::{InstancedRenderObject, Instance, RenderObject},
     resources::Resources,
     transform::{Basis, Transform},
     light::Light,
@@ -20,8 +21,13 @@ use crate::{
     math::*,
     renderer::Renderer,
     managers::Manager,
+    gltf_loader::ImportedScene,
+    memory::{MemoryReport, References},
 };
 
+// F9 prints the memory report, F10 dumps it here
+const MEMORY_REPORT_PATH: &str = "memory_report.json";
+
 pub struct Player {
     transform: Transform,
     basis: Basis,
@@ -44,7 +50,7 @@ impl Player {
                     0.9, 
                     0.01, 
                     0.1),
-            )
+            ).unwrap_or_else(|error| panic!("{}", error))
         }
     }
     pub fn update(&mut self, manager: &Manager, dt: i32) {
@@ -100,6 +106,9 @@ impl Player {
     pub fn draw(&self, res: &Resources, camera: &Camera) {
         self.gun.draw(res, camera);
     }
+    pub fn collect_references(&self, references: &mut References) {
+        self.gun.collect_references(references);
+    }
     pub fn front(&self) -> Vector3f {
         self.basis.transform_basis(&self.transform).v3
     }
@@ -107,7 +116,7 @@ impl Player {
 pub struct Scene {
     pub targets: Vec::<Target>,
     pub objects: Vec::<RenderObject>,
-    pub terrain_chunks: Vec::<TerrainChunkObject>,
+    pub instanced_objects: Vec::<InstancedRenderObject>,
     pub lights: Vec::<Light>,
     pub camera: Camera,
     pub player: Player,
@@ -118,7 +127,7 @@ impl Scene {
         let mut scene = Scene {
             targets: Vec::new(),
             objects: Vec::new(),
-            terrain_chunks: Vec::new(),
+            instanced_objects: Vec::new(),
             lights: Vec::new(),
             camera,
             player: Player::new(res),
@@ -128,27 +137,33 @@ impl Scene {
     }
     pub fn init_scene(&mut self, res: &Resources) {
 
-        for x in 0..1 {
-            for z in 0..1 {
-                let mut transform = Transform::identity();
-                transform.translation.x = (x - 50) as f32;
-                transform.translation.z = (z - 50) as f32;
-                transform.translation.y = -7f32;
-
-                self.terrain_chunks.push(TerrainChunkObject::new(
-                    res,
-                    transform,
-                    "g_terrain_program",
-                    "cube",
-                    Material::make_basic_material(
-                        Vector3f::new(0f32, 0.4f32, 0f32),
-                        0.3f32,
-                        0.8f32,
-                        0.2f32
-                    )
-                ))
+        // terrain, a 100 x 500 grid of cubes in one draw
+        let mut terrain = Vec::with_capacity(100 * 500);
+        for x in 0..100 {
+            for z in 0..500 {
+                let mut instance = Instance::new(Transform::identity());
+                instance.transform.translation.x = x as f32;
+                instance.transform.translation.z = z as f32;
+                terrain.push(instance);
             }
         }
+        let mut transform = Transform::identity();
+        transform.translation.x = -50f32;
+        transform.translation.z = -50f32;
+        transform.translation.y = -7f32;
+        self.instanced_objects.push(InstancedRenderObject::new(
+            res,
+            transform,
+            "g_instanced_program",
+            "cube",
+            Material::make_basic_material(
+                Vector3f::new(0f32, 0.4f32, 0f32),
+                0.3f32,
+                0.8f32,
+                0.2f32
+            ),
+            terrain
+        ).unwrap_or_else(|error| panic!("{}", error)));
 
         for x in 0..10 {
             for y in 0..10 {
@@ -180,13 +195,8 @@ impl Scene {
             transform,
             "g_tex_program",
             "lab",
-            Material {
-                albedo: Albedo(MaterialPropertyType::FromTexture(res.get_texture_id_by_name("dumb").unwrap())),
-                normal: Normal(MaterialPropertyType::PerVertex),
-                metallic: Metallic(MaterialPropertyType::Constant(1f32)),
-                roughness: Roughness(MaterialPropertyType::Constant(0.2f32)),
-                ao: AO(MaterialPropertyType::Constant(1f32)),
-            }));
+            res.get_material_by_name("lab").unwrap().clone()
+        ).unwrap_or_else(|error| panic!("{}", error)));
 
         for x in 0..2 {
             for y in 0..2 {
@@ -214,12 +224,29 @@ impl Scene {
     pub fn add_light(&mut self, light: Light) {
         self.lights.push(light);
     }
+    pub fn add_imported_scene(&mut self, imported: ImportedScene) {
+        self.objects.extend(imported.objects);
+        self.lights.extend(imported.lights);
+    }
     pub fn get_objects(&self) -> &Vec<RenderObject> {
         &self.objects
     }
     pub fn get_lights(&self) -> &Vec<Light> {
         &self.lights
     }
+    // every resource handle the scene draws with
+    pub fn collect_references(&self, references: &mut References) {
+        for target in self.targets.iter() {
+            target.collect_references(references);
+        }
+        for obj in self.objects.iter() {
+            obj.collect_references(references);
+        }
+        for obj in self.instanced_objects.iter() {
+            obj.collect_references(references);
+        }
+        self.player.collect_references(references);
+    }
     pub fn update(&mut self, manager: &Manager, dt: i32) {
 
 
@@ -233,7 +260,7 @@ impl Scene {
                 direction: self.player.front(),
             };
             for target in self.targets.iter_mut() {
-                if target.ray_intersection(&ray) {
+                if target.ray_intersection(manager.get_res(), &ray) {
                     target.set_hit();
                 }
             }
@@ -272,7 +299,8 @@ impl Target {
                 transform,
                 "g_program",
                 "sphere",
-                material),
+                material
+            ).unwrap_or_else(|error| panic!("{}", error)),
                 dt: 0f32,
             }
     }
@@ -283,21 +311,10 @@ impl Target {
         }
         self.sphere_render_object.material.albedo = Albedo(MaterialPropertyType::Constant(Vector3f::interpolate(&Vector3f::new(1f32, 0f32, 0f32), &Vector3f::new(0f32, 1f32, 0f32), self.dt)));
     }
-    pub fn ray_intersection(&self, ray: &Ray) -> bool {
-        let so = self.sphere_render_object.transform.translation;
-        let sr = 0.5f32; // really very bad trial and error guessing what the radius of the scaled sphere meshes is without even taking into account the possible scale factor from constructor
-
-        let diffo = Vector3f::sub(&ray.origin, &so);
-
-        let p = Vector3f::dot(&ray.direction, &diffo);
-        let q = Vector3f::dot(&diffo, &diffo) - sr * sr;
-
-        let disc = p * p - q;
-        if disc < 0f32 {
-            return false;
-        } else {
-            return true;
-        }
+    pub fn ray_intersection(&self, res: &Resources, ray: &Ray) -> bool {
+        self.sphere_render_object.world_bounding_sphere(res)
+            .ray_intersection(&ray.origin, &ray.direction)
+            .is_some()
     }
     pub fn set_hit(&mut self) {
         self.dt = 1f32;
@@ -305,6 +322,9 @@ impl Target {
     pub fn draw(&self, res: &Resources, camera: &Camera) {
         self.sphere_render_object.draw(res, camera);
     }
+    pub fn collect_references(&self, references: &mut References) {
+        self.sphere_render_object.collect_references(references);
+    }
 }
 pub struct GameState {
     scene: Scene,
@@ -325,6 +345,16 @@ impl GameState {
     pub fn render(&self, manager: &Manager, renderer: &Renderer) {
         renderer.render(manager.get_res(), &self.scene);
     }
+    pub fn add_imported_scene(&mut self, imported: ImportedScene) {
+        self.scene.add_imported_scene(imported);
+    }
+    pub fn memory_report(&self, manager: &Manager, renderer: &Renderer) -> MemoryReport {
+        let mut references = References::new();
+        self.scene.collect_references(&mut references);
+        let mut report = manager.get_res().memory_report(&references);
+        report.extend(renderer.memory_entries());
+        report
+    }
 }
 
 pub struct GameApp {
@@ -366,12 +396,17 @@ impl GameApp {
         sdl_mouse_util.set_relative_mouse_mode(true);
         let mut event_pump = sdl_context.event_pump().unwrap();
     
-        let manager = Manager::new();
+        let mut manager = Manager::new();
 
         let renderer = Renderer::new(window_width, window_height);
 
+        let mut game_state = GameState::new(manager.get_res(), window_width, window_height);
+        for imported in manager.take_imported_scenes() {
+            game_state.add_imported_scene(imported);
+        }
+
         GameApp {
-            game_state: GameState::new(manager.get_res(), window_width, window_height),
+            game_state,
             sdl_context,
             window,
             window_width: window_width as i32,
@@ -392,6 +427,16 @@ impl GameApp {
                     Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                         break 'running
                     },
+                    Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+                        println!("{}", self.game_state.memory_report(&self.manager, &self.renderer));
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F10), repeat: false, .. } => {
+                        let report = self.game_state.memory_report(&self.manager, &self.renderer);
+                        match report.save_json(Path::new(MEMORY_REPORT_PATH), report.entries().len()) {
+                            Ok(_) => println!("Wrote {}", MEMORY_REPORT_PATH),
+                            Err(error) => println!("Could not write {}: {}", MEMORY_REPORT_PATH, error),
+                        }
+                    },
                     _ => {
                         self.manager.input.update(&event);
                     }
@@ -413,6 +458,9 @@ impl GameApp {
         self.game_loop();
     }
     pub fn update(&mut self) {
+        self.manager.upload_pending_assets();
+        self.manager.poll_asset_changes();
+        self.manager.unload_released_assets();
         self.manager.timer.update();
         self.game_state.update(&self.manager, self.manager.timer.get_elapsed_ms());
     }
@@ -0,0 +1,118 @@
+/*
+helpers for the passes that add or rewrite a vertex attribute of a MeshData
+they work on the cpu side f32 vertices, packed attributes are read and written unpacked like everywhere else
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::math::Vector3f;
+use crate::mesh::MeshData;
+use crate::vertex::{VertexLayout, AttributeType, PrimitiveMode};
+
+pub const POSITION: [AttributeType; 1] = [AttributeType::Position3D];
+pub const NORMAL: [AttributeType; 2] = [AttributeType::Normal3D, AttributeType::Normal3DPacked];
+pub const TEX_COORD: [AttributeType; 2] = [AttributeType::ST, AttributeType::STHalf];
+pub const TANGENT: [AttributeType; 2] = [AttributeType::Tangent4D, AttributeType::Tangent4DPacked];
+
+// positions closer than this count as the same point when finding neighbours across seams
+const WELD_DISTANCE: f32 = 1e-4;
+
+#[derive(Debug)]
+pub enum Error {
+    MissingAttribute(&'static str),
+    // lines and points
+    NotTriangles,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingAttribute(name) => write!(f, "mesh has no {} attribute", name),
+            Error::NotTriangles => write!(f, "mesh has no triangles"),
+        }
+    }
+}
+
+pub fn require(layout: &VertexLayout, types: &[AttributeType], name: &'static str) -> Result<usize, Error> {
+    layout.component_offset(types).ok_or(Error::MissingAttribute(name))
+}
+
+pub fn read_vec2(data: &MeshData, vertex: usize, offset: usize) -> (f32, f32) {
+    let start = vertex * data.vertex_layout.get_num_components() + offset;
+    (data.vertices[start], data.vertices[start + 1])
+}
+
+pub fn read_vec3(data: &MeshData, vertex: usize, offset: usize) -> Vector3f {
+    let start = vertex * data.vertex_layout.get_num_components() + offset;
+    Vector3f::new(data.vertices[start], data.vertices[start + 1], data.vertices[start + 2])
+}
+
+/*
+inserted before the attribute at index, which moves the locations of everything after it
+so pick the index the shaders expect, e.g. the normal right after the position, zero filled, returns its offset
+*/
+pub fn add_attribute(data: &mut MeshData, attribute: AttributeType, index: usize) -> usize {
+    let old_width = data.vertex_layout.get_num_components();
+    let added = attribute.num_components();
+    let num_vertices = data.vertices.len() / old_width.max(1);
+    let mut types = data.vertex_layout.types().to_vec();
+    let offset: usize = types[..index].iter().map(|t| t.num_components()).sum();
+
+    let mut vertices = Vec::with_capacity(num_vertices * (old_width + added));
+    for vertex in data.vertices.chunks(old_width) {
+        vertices.extend_from_slice(&vertex[..offset]);
+        vertices.resize(vertices.len() + added, 0f32);
+        vertices.extend_from_slice(&vertex[offset..]);
+    }
+    types.insert(index, attribute);
+
+    data.vertices = vertices;
+    data.vertex_layout = VertexLayout::new(types);
+    offset
+}
+
+// one id per vertex, the same for vertices at the same position
+pub fn position_groups(data: &MeshData, position: usize) -> Vec<usize> {
+    let width = data.vertex_layout.get_num_components();
+    let num_vertices = data.vertices.len() / width.max(1);
+    let quantize = |value: f32| (value / WELD_DISTANCE).round() as i64;
+
+    let mut ids: HashMap<(i64, i64, i64), usize> = HashMap::new();
+    let mut groups = Vec::with_capacity(num_vertices);
+    for vertex in 0..num_vertices {
+        let p = read_vec3(data, vertex, position);
+        let key = (quantize(p.x), quantize(p.y), quantize(p.z));
+        let next = ids.len();
+        groups.push(*ids.entry(key).or_insert(next));
+    }
+    groups
+}
+
+/*
+rewrites the mesh as an indexed triangle list where corner i of triangles gets values[i * width..(i + 1) * width] at offset
+corners of one vertex that ended up with the same values keep sharing it, the others get a copy of the vertex
+*/
+pub fn split_corners(data: &mut MeshData, triangles: &[i32], offset: usize, width: usize, values: &[f32]) {
+    let vertex_width = data.vertex_layout.get_num_components();
+    let mut vertices: Vec<f32> = Vec::with_capacity(data.vertices.len());
+    let mut indices: Vec<i32> = Vec::with_capacity(triangles.len());
+    let mut remap: HashMap<(i32, Vec<u32>), i32> = HashMap::new();
+
+    for (corner, source) in triangles.iter().enumerate() {
+        let value = &values[corner * width..(corner + 1) * width];
+        let key = (*source, value.iter().map(|v| v.to_bits()).collect());
+        let index = *remap.entry(key).or_insert_with(|| {
+            let start = *source as usize * vertex_width;
+            let first = vertices.len();
+            vertices.extend_from_slice(&data.vertices[start..start + vertex_width]);
+            vertices[first + offset..first + offset + width].copy_from_slice(value);
+            (first / vertex_width) as i32
+        });
+        indices.push(index);
+    }
+
+    data.vertices = vertices;
+    data.indices = indices;
+    data.primitive = PrimitiveMode::Triangles;
+}
@@ -0,0 +1,138 @@
+/*
+pieces the primitive generators are made of
+everything is Position3D Normal3D ST, counter clockwise seen from the side the normal points to
+grids and quads put u along their first direction and v along the second, caps may end up mirrored which the tangent sign covers
+*/
+
+use std::f32::consts::PI;
+
+use crate::math::{Vector2f, Vector3f};
+use crate::mesh::{Mesh, MeshData};
+use crate::vertex::{VertexLayout, AttributeType};
+use super::tangents::compute_tangents;
+
+pub const VERTEX_WIDTH: usize = 8;
+
+pub fn push_vertex(vertices: &mut Vec<f32>, position: &Vector3f, normal: &Vector3f, u: f32, v: f32) {
+    vertices.extend_from_slice(&[position.x, position.y, position.z, normal.x, normal.y, normal.z, u, v]);
+}
+
+pub fn num_vertices(vertices: &[f32]) -> i32 {
+    (vertices.len() / VERTEX_WIDTH) as i32
+}
+
+// two triangles per cell of a (columns + 1) x (rows + 1) grid of vertices starting at first, row by row
+pub fn push_grid_indices(indices: &mut Vec<i32>, first: i32, columns: i32, rows: i32) {
+    let row_width = columns + 1;
+    for y in 0..rows {
+        for x in 0..columns {
+            let a = first + y * row_width + x;
+            let b = a + 1;
+            let d = a + row_width;
+            let e = d + 1;
+            indices.extend_from_slice(&[a, b, e, a, e, d]);
+        }
+    }
+}
+
+// flat grid from origin spanning u_axis and v_axis, facing u_axis x v_axis, uvs 0 to 1
+pub fn push_grid(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, origin: &Vector3f, u_axis: &Vector3f, v_axis: &Vector3f, columns: i32, rows: i32) {
+    let columns = columns.max(1);
+    let rows = rows.max(1);
+    let mut normal = Vector3f::cross(u_axis, v_axis);
+    normal.normalize();
+
+    let first = num_vertices(vertices);
+    for y in 0..=rows {
+        for x in 0..=columns {
+            let u = x as f32 / columns as f32;
+            let v = y as f32 / rows as f32;
+            let mut position = origin.copy();
+            position.add_to(&Vector3f::new(u_axis.x * u, u_axis.y * u, u_axis.z * u));
+            position.add_to(&Vector3f::new(v_axis.x * v, v_axis.y * v, v_axis.z * v));
+            push_vertex(vertices, &position, &normal, u, v);
+        }
+    }
+    push_grid_indices(indices, first, columns, rows);
+}
+
+// four vertices of its own so the edges stay hard, corners in order around the quad, facing (b - a) x (d - a)
+pub fn push_quad(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, corners: [Vector3f; 4], uvs: [(f32, f32); 4]) {
+    let mut normal = Vector3f::cross(&Vector3f::sub(&corners[1], &corners[0]), &Vector3f::sub(&corners[3], &corners[0]));
+    if normal.is_zero() {
+        return;
+    }
+    normal.normalize();
+    let first = num_vertices(vertices);
+    for (corner, uv) in corners.iter().zip(uvs.iter()) {
+        push_vertex(vertices, corner, &normal, uv.0, uv.1);
+    }
+    indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+}
+
+// one row of a surface of revolution around y, position.x is the radius
+#[derive(Debug, Clone, Copy)]
+pub struct ProfilePoint {
+    pub position: Vector2f,
+    // outward in the same radius / height plane, unit length
+    pub normal: Vector2f,
+    pub v: f32,
+}
+
+// the point at angle around y, the direction of growing angle makes the surface face outwards
+pub fn around_y(radius: f32, y: f32, angle: f32) -> Vector3f {
+    Vector3f::new(radius * angle.cos(), y, -radius * angle.sin())
+}
+
+/*
+profile rows from bottom to top swept once around y
+the seam column is doubled so u runs 0 to 1, rows where the radius is 0 end up as fans of degenerate triangles
+*/
+pub fn push_revolution(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, profile: &[ProfilePoint], segments: i32) {
+    let segments = segments.max(3);
+    let first = num_vertices(vertices);
+    for point in profile.iter() {
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let angle = u * 2f32 * PI;
+            let position = around_y(point.position.x, point.position.y, angle);
+            let normal = around_y(point.normal.x, point.normal.y, angle);
+            push_vertex(vertices, &position, &normal, u, point.v);
+        }
+    }
+    if profile.len() > 1 {
+        push_grid_indices(indices, first, segments, profile.len() as i32 - 1);
+    }
+}
+
+// flat cap at height y facing up or down, uvs are the disk mapped into the unit square
+pub fn push_disk(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, y: f32, radius: f32, segments: i32, facing_up: bool) {
+    let segments = segments.max(3);
+    let normal = Vector3f::new(0f32, if facing_up { 1f32 } else { -1f32 }, 0f32);
+    let v_sign = if facing_up { 1f32 } else { -1f32 };
+
+    let center = num_vertices(vertices);
+    push_vertex(vertices, &Vector3f::new(0f32, y, 0f32), &normal, 0.5, 0.5);
+    for j in 0..=segments {
+        let angle = j as f32 / segments as f32 * 2f32 * PI;
+        push_vertex(vertices, &around_y(radius, y, angle), &normal, 0.5 + 0.5 * angle.cos(), 0.5 + 0.5 * v_sign * angle.sin());
+    }
+    for j in 0..segments {
+        let ring = center + 1 + j;
+        if facing_up {
+            indices.extend_from_slice(&[center, ring, ring + 1]);
+        } else {
+            indices.extend_from_slice(&[center, ring + 1, ring]);
+        }
+    }
+}
+
+// tangents are derived from the uvs, normals are expected to be right already
+pub fn finish(vertices: Vec<f32>, indices: Vec<i32>) -> Mesh {
+    let vertex_layout = VertexLayout::new(vec![AttributeType::Position3D, AttributeType::Normal3D, AttributeType::ST]);
+    let mut data = MeshData::new(vertices, indices, vertex_layout);
+    if !data.indices.is_empty() {
+        compute_tangents(&mut data).unwrap();
+    }
+    Mesh::new(data)
+}
@@ -0,0 +1,56 @@
+/*
+    Capsule around y, centered on the origin
+    height is the straight part between the two half spheres, total height is height + 2 * radius
+    v follows the length of the profile so the texture isn't stretched over the caps
+*/
+use std::f32::consts::PI;
+
+use crate::math::Vector2f;
+use crate::mesh::Mesh;
+use super::builder::{ProfilePoint, push_revolution, finish};
+
+pub fn gen_capsule_mesh(radius: f32, height: f32, radial_segments: i32, cap_segments: i32, height_segments: i32) -> Mesh {
+    let cap_segments = cap_segments.max(1);
+    let height_segments = height_segments.max(1);
+    let half = height * 0.5;
+    let profile_length = PI * radius + height;
+    let cap_length = 0.5 * PI * radius;
+
+    let mut profile: Vec<ProfilePoint> = Vec::with_capacity((2 * cap_segments + height_segments + 1) as usize);
+    // bottom cap from the pole up to the equator
+    for i in 0..cap_segments {
+        let t = i as f32 / cap_segments as f32;
+        let angle = -0.5 * PI + t * 0.5 * PI;
+        // exactly on the axis at the pole, cos doesn't get there
+        let normal = if i == 0 { Vector2f::new(0f32, -1f32) } else { Vector2f::new(angle.cos(), angle.sin()) };
+        profile.push(ProfilePoint {
+            position: Vector2f::new(radius * normal.x, -half + radius * normal.y),
+            normal,
+            v: t * cap_length / profile_length,
+        });
+    }
+    for i in 0..height_segments {
+        let t = i as f32 / height_segments as f32;
+        profile.push(ProfilePoint {
+            position: Vector2f::new(radius, -half + t * height),
+            normal: Vector2f::new(1f32, 0f32),
+            v: (cap_length + t * height) / profile_length,
+        });
+    }
+    // top cap from the equator to the pole
+    for i in 0..=cap_segments {
+        let t = i as f32 / cap_segments as f32;
+        let angle = t * 0.5 * PI;
+        let normal = if i == cap_segments { Vector2f::new(0f32, 1f32) } else { Vector2f::new(angle.cos(), angle.sin()) };
+        profile.push(ProfilePoint {
+            position: Vector2f::new(radius * normal.x, half + radius * normal.y),
+            normal,
+            v: (cap_length + height + t * cap_length) / profile_length,
+        });
+    }
+
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+    push_revolution(&mut vertices, &mut indices, &profile, radial_segments);
+    finish(vertices, indices)
+}
@@ -0,0 +1,33 @@
+/*
+    Cone around y, base at y = 0 and tip at y = height
+    the tip gets one vertex per segment so every side keeps its own normal and u
+*/
+use crate::math::Vector2f;
+use crate::mesh::Mesh;
+use super::builder::{ProfilePoint, push_revolution, push_disk, finish};
+
+pub fn gen_cone_mesh(radius: f32, height: f32, radial_segments: i32, height_segments: i32, base: bool) -> Mesh {
+    let height_segments = height_segments.max(1);
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+
+    // the side's normal is the same all the way up
+    let mut normal = Vector2f::new(height, radius);
+    normal.normalize();
+    let profile: Vec<ProfilePoint> = (0..=height_segments)
+        .map(|i| {
+            let v = i as f32 / height_segments as f32;
+            ProfilePoint {
+                position: Vector2f::new(radius * (1f32 - v), height * v),
+                normal,
+                v,
+            }
+        })
+        .collect();
+    push_revolution(&mut vertices, &mut indices, &profile, radial_segments);
+
+    if base {
+        push_disk(&mut vertices, &mut indices, 0f32, radius, radial_segments, false);
+    }
+    finish(vertices, indices)
+}
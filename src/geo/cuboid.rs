@@ -0,0 +1,27 @@
+/*
+    Box centered on the origin, every face its own grid with 0 to 1 uvs so a texture shows once per face
+*/
+use crate::math::Vector3f;
+use crate::mesh::Mesh;
+use super::builder::{push_grid, finish, VERTEX_WIDTH};
+
+pub fn gen_cuboid_mesh(size: Vector3f, segments: i32) -> Mesh {
+    let segments = segments.max(1);
+    let (x, y, z) = (size.x * 0.5, size.y * 0.5, size.z * 0.5);
+    let mut vertices: Vec<f32> = Vec::with_capacity(6 * VERTEX_WIDTH * ((segments + 1) * (segments + 1)) as usize);
+    let mut indices: Vec<i32> = Vec::with_capacity(6 * 6 * (segments * segments) as usize);
+
+    // (corner, u axis, v axis), u x v points out of the box
+    let faces = [
+        (Vector3f::new(x, -y, z), Vector3f::new(0f32, 0f32, -size.z), Vector3f::new(0f32, size.y, 0f32)),
+        (Vector3f::new(-x, -y, -z), Vector3f::new(0f32, 0f32, size.z), Vector3f::new(0f32, size.y, 0f32)),
+        (Vector3f::new(-x, y, z), Vector3f::new(size.x, 0f32, 0f32), Vector3f::new(0f32, 0f32, -size.z)),
+        (Vector3f::new(-x, -y, -z), Vector3f::new(size.x, 0f32, 0f32), Vector3f::new(0f32, 0f32, size.z)),
+        (Vector3f::new(-x, -y, z), Vector3f::new(size.x, 0f32, 0f32), Vector3f::new(0f32, size.y, 0f32)),
+        (Vector3f::new(x, -y, -z), Vector3f::new(-size.x, 0f32, 0f32), Vector3f::new(0f32, size.y, 0f32)),
+    ];
+    for (corner, u_axis, v_axis) in faces.iter() {
+        push_grid(&mut vertices, &mut indices, corner, u_axis, v_axis, segments, segments);
+    }
+    finish(vertices, indices)
+}
@@ -0,0 +1,31 @@
+/*
+    Cylinder around y, centered on the origin
+    the caps have their own vertices so the rim stays a hard edge
+*/
+use crate::math::Vector2f;
+use crate::mesh::Mesh;
+use super::builder::{ProfilePoint, push_revolution, push_disk, finish};
+
+pub fn gen_cylinder_mesh(radius: f32, height: f32, radial_segments: i32, height_segments: i32, caps: bool) -> Mesh {
+    let height_segments = height_segments.max(1);
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+
+    let profile: Vec<ProfilePoint> = (0..=height_segments)
+        .map(|i| {
+            let v = i as f32 / height_segments as f32;
+            ProfilePoint {
+                position: Vector2f::new(radius, (v - 0.5) * height),
+                normal: Vector2f::new(1f32, 0f32),
+                v,
+            }
+        })
+        .collect();
+    push_revolution(&mut vertices, &mut indices, &profile, radial_segments);
+
+    if caps {
+        push_disk(&mut vertices, &mut indices, height * 0.5, radius, radial_segments, true);
+        push_disk(&mut vertices, &mut indices, -height * 0.5, radius, radial_segments, false);
+    }
+    finish(vertices, indices)
+}
@@ -0,0 +1,174 @@
+/*
+    Walls pulled up from a floor plan
+    outline points are x, z on the floor and go up from y = 0 to height
+    every wall is its own quad for hard corners, uvs are in world units so textures keep their size on long walls
+*/
+use crate::math::{Vector2f, Vector3f};
+use crate::mesh::Mesh;
+use super::builder::{push_quad, push_vertex, num_vertices, finish};
+use super::triangulate::{triangulate, signed_area};
+
+fn wall(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, a: &Vector2f, b: &Vector2f, u: f32, height: f32) -> f32 {
+    let length = Vector2f::new(b.x - a.x, b.y - a.y).mag();
+    let corners = [
+        Vector3f::new(a.x, 0f32, a.y),
+        Vector3f::new(b.x, 0f32, b.y),
+        Vector3f::new(b.x, height, b.y),
+        Vector3f::new(a.x, height, a.y),
+    ];
+    let uvs = [(u, 0f32), (u + length, 0f32), (u + length, height), (u, height)];
+    push_quad(vertices, indices, corners, uvs);
+    u + length
+}
+
+// flat cap, uvs are the floor position
+fn cap(vertices: &mut Vec<f32>, indices: &mut Vec<i32>, outline: &[Vector2f], y: f32, facing_up: bool) {
+    let normal = Vector3f::new(0f32, if facing_up { 1f32 } else { -1f32 }, 0f32);
+    let first = num_vertices(vertices);
+    for point in outline.iter() {
+        push_vertex(vertices, &Vector3f::new(point.x, y, point.y), &normal, point.x, point.y);
+    }
+    // counter clockwise on the plan is clockwise seen from above
+    for [a, b, c] in triangulate(outline).iter().copied() {
+        if facing_up {
+            indices.extend_from_slice(&[first + a as i32, first + c as i32, first + b as i32]);
+        } else {
+            indices.extend_from_slice(&[first + a as i32, first + b as i32, first + c as i32]);
+        }
+    }
+}
+
+// closed outline, either winding, walls face out and caps close the top and bottom
+pub fn gen_extrude_mesh(outline: &[Vector2f], height: f32, caps: bool) -> Mesh {
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+
+    // walls face out when the outline runs clockwise on the plan
+    let mut points = outline.to_vec();
+    if signed_area(&points) > 0f32 {
+        points.reverse();
+    }
+    let mut u = 0f32;
+    for i in 0..points.len() {
+        u = wall(&mut vertices, &mut indices, &points[i], &points[(i + 1) % points.len()], u, height);
+    }
+    if caps && points.len() >= 3 {
+        cap(&mut vertices, &mut indices, &points, height, true);
+        cap(&mut vertices, &mut indices, &points, 0f32, false);
+    }
+    finish(vertices, indices)
+}
+
+// left of the segment from a to b, unit length
+fn segment_normal(a: &Vector2f, b: &Vector2f) -> Vector2f {
+    let mut normal = Vector2f::new(a.y - b.y, b.x - a.x);
+    if !normal.is_zero() {
+        normal.normalize();
+    }
+    normal
+}
+
+// longest a sharp corner may stick out, in half thicknesses
+const MITER_LIMIT: f32 = 4f32;
+
+/*
+open line of walls with the given thickness centered on the line, so floor plans can be drawn as wall center lines
+turns are mitered, the ends are cut square
+*/
+pub fn gen_extrude_polyline_mesh(line: &[Vector2f], thickness: f32, height: f32) -> Mesh {
+    if line.len() < 2 {
+        return finish(Vec::new(), Vec::new());
+    }
+    let half = thickness * 0.5;
+    let last = line.len() - 1;
+    let mut left: Vec<Vector2f> = Vec::with_capacity(line.len());
+    let mut right: Vec<Vector2f> = Vec::with_capacity(line.len());
+    for i in 0..line.len() {
+        let before = if i > 0 { segment_normal(&line[i - 1], &line[i]) } else { segment_normal(&line[i], &line[i + 1]) };
+        let after = if i < last { segment_normal(&line[i], &line[i + 1]) } else { before };
+        let mut miter = Vector2f::new(before.x + after.x, before.y + after.y);
+        if miter.is_zero() {
+            miter = before;
+        }
+        // repeated points
+        if miter.is_zero() {
+            continue;
+        }
+        miter.normalize();
+        let scale = half / Vector2f::dot(miter, after).max(1f32 / MITER_LIMIT);
+        left.push(Vector2f::new(line[i].x + miter.x * scale, line[i].y + miter.y * scale));
+        right.push(Vector2f::new(line[i].x - miter.x * scale, line[i].y - miter.y * scale));
+    }
+    right.reverse();
+    left.extend(right);
+    gen_extrude_mesh(&left, height, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::attributes::{read_vec3, POSITION, NORMAL};
+
+    fn l_outline() -> Vec<Vector2f> {
+        [(0f32, 0f32), (2f32, 0f32), (2f32, 1f32), (1f32, 1f32), (1f32, 2f32), (0f32, 2f32)].iter()
+            .map(|(x, y)| Vector2f::new(*x, *y))
+            .collect()
+    }
+
+    // every triangle winds counter clockwise around its normal, walls face away from the outline, caps up and down
+    fn check(outline: &[Vector2f]) {
+        let height = 3f32;
+        let mesh = gen_extrude_mesh(outline, height, true);
+        let data = mesh.data();
+        let position = data.vertex_layout.component_offset(&POSITION).unwrap();
+        let normal_offset = data.vertex_layout.component_offset(&NORMAL).unwrap();
+        let (mut walls, mut tops, mut bottoms) = (0, 0, 0);
+        for triangle in data.indices.chunks_exact(3) {
+            let p: Vec<Vector3f> = triangle.iter().map(|i| read_vec3(data, *i as usize, position)).collect();
+            let normal = read_vec3(data, triangle[0] as usize, normal_offset);
+            let winding = Vector3f::cross(&Vector3f::sub(&p[1], &p[0]), &Vector3f::sub(&p[2], &p[0]));
+            assert!(Vector3f::dot(&winding, &normal) > 0f32, "triangle {:?} winds against its normal", triangle);
+
+            if normal.y > 0.5 {
+                assert!(p.iter().all(|q| q.y == height));
+                tops += 1;
+            } else if normal.y < -0.5 {
+                assert!(p.iter().all(|q| q.y == 0f32));
+                bottoms += 1;
+            } else {
+                // a point just outside the wall's middle is outside the outline
+                let x = (p[0].x + p[1].x + p[2].x) / 3f32 + normal.x * 0.01;
+                let z = (p[0].z + p[1].z + p[2].z) / 3f32 + normal.z * 0.01;
+                assert!(!inside(outline, x, z), "wall triangle {:?} faces in", triangle);
+                walls += 1;
+            }
+        }
+        assert_eq!(walls, outline.len() * 2);
+        assert_eq!(tops, outline.len() - 2);
+        assert_eq!(bottoms, outline.len() - 2);
+    }
+
+    // even odd rule
+    fn inside(outline: &[Vector2f], x: f32, z: f32) -> bool {
+        let mut inside = false;
+        for i in 0..outline.len() {
+            let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+            if (a.y > z) != (b.y > z) && x < a.x + (z - a.y) / (b.y - a.y) * (b.x - a.x) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    #[test]
+    fn counter_clockwise_outline() {
+        check(&l_outline());
+    }
+
+    #[test]
+    fn clockwise_outline() {
+        let mut outline = l_outline();
+        outline.reverse();
+        check(&outline);
+    }
+}
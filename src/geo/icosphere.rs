@@ -0,0 +1,101 @@
+/*
+    Icosphere, an icosahedron with every triangle split in four per subdivision and pushed out to the sphere
+    triangles are close to the same size everywhere, unlike the lathe sphere which bunches up at the poles
+    uvs are the same equirectangular mapping as the lathe sphere, vertices on the seam and at the poles are split for it
+*/
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use crate::math::Vector3f;
+use crate::mesh::Mesh;
+use super::builder::{push_vertex, num_vertices, finish, VERTEX_WIDTH};
+
+fn icosahedron() -> (Vec<Vector3f>, Vec<[usize; 3]>) {
+    let t = (1f32 + 5f32.sqrt()) * 0.5;
+    let points = vec![
+        Vector3f::new(-1f32, t, 0f32), Vector3f::new(1f32, t, 0f32), Vector3f::new(-1f32, -t, 0f32), Vector3f::new(1f32, -t, 0f32),
+        Vector3f::new(0f32, -1f32, t), Vector3f::new(0f32, 1f32, t), Vector3f::new(0f32, -1f32, -t), Vector3f::new(0f32, 1f32, -t),
+        Vector3f::new(t, 0f32, -1f32), Vector3f::new(t, 0f32, 1f32), Vector3f::new(-t, 0f32, -1f32), Vector3f::new(-t, 0f32, 1f32),
+    ];
+    let faces = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+    (points, faces)
+}
+
+fn unit(mut v: Vector3f) -> Vector3f {
+    v.normalize();
+    v
+}
+
+// u around y in the same direction as the other generators, v from the bottom pole to the top
+fn sphere_uv(p: &Vector3f) -> (f32, f32) {
+    let angle = (-p.z).atan2(p.x);
+    let u = if angle < 0f32 { angle / (2f32 * PI) + 1f32 } else { angle / (2f32 * PI) };
+    let v = 0.5 + p.y.clamp(-1f32, 1f32).asin() / PI;
+    (u, v)
+}
+
+pub fn gen_icosphere_mesh(radius: f32, subdivisions: i32) -> Mesh {
+    let (mut points, mut faces) = icosahedron();
+    for point in points.iter_mut() {
+        *point = unit(*point);
+    }
+
+    for _ in 0..subdivisions.max(0) {
+        // shared edges get one midpoint
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, points: &mut Vec<Vector3f>| -> usize {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                points.push(unit(Vector3f::interpolate(&points[a], &points[b], 0.5)));
+                points.len() - 1
+            })
+        };
+        let mut subdivided = Vec::with_capacity(faces.len() * 4);
+        for [a, b, c] in faces.iter().copied() {
+            let ab = midpoint(a, b, &mut points);
+            let bc = midpoint(b, c, &mut points);
+            let ca = midpoint(c, a, &mut points);
+            subdivided.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+        faces = subdivided;
+    }
+
+    let mut vertices: Vec<f32> = Vec::with_capacity(points.len() * VERTEX_WIDTH);
+    let mut indices: Vec<i32> = Vec::with_capacity(faces.len() * 3);
+    let mut corner_vertices: HashMap<(usize, u32), i32> = HashMap::new();
+    for face in faces.iter() {
+        let mut uvs: Vec<(f32, f32)> = face.iter().map(|i| sphere_uv(&points[*i])).collect();
+        let pole: Vec<bool> = face.iter().map(|i| points[*i].y.abs() > 0.9999).collect();
+        // a triangle across the seam would stretch over the whole texture, move its low side past 1
+        let side_u: Vec<f32> = (0..3).filter(|k| !pole[*k]).map(|k| uvs[k].0).collect();
+        let max_u = side_u.iter().copied().fold(0f32, f32::max);
+        let min_u = side_u.iter().copied().fold(1f32, f32::min);
+        if max_u - min_u > 0.5 {
+            for k in (0..3).filter(|k| !pole[*k]) {
+                if uvs[k].0 < 0.5 {
+                    uvs[k].0 += 1f32;
+                }
+            }
+        }
+        // at a pole u means nothing, take the middle of the other two
+        for k in (0..3).filter(|k| pole[*k]) {
+            uvs[k].0 = 0.5 * (uvs[(k + 1) % 3].0 + uvs[(k + 2) % 3].0);
+        }
+        for k in 0..3 {
+            let point = points[face[k]];
+            let (u, v) = uvs[k];
+            let index = *corner_vertices.entry((face[k], u.to_bits())).or_insert_with(|| {
+                let position = Vector3f::new(point.x * radius, point.y * radius, point.z * radius);
+                push_vertex(&mut vertices, &position, &point, u, v);
+                num_vertices(&vertices) - 1
+            });
+            indices.push(index);
+        }
+    }
+    finish(vertices, indices)
+}
@@ -1,8 +1,10 @@
 // create lathe geometry
 
 use crate::math::{Vector2f};
-use crate::mesh::Mesh;
-use crate::vertex::{VertexLayout, AttributeType, Vertex};
+use crate::mesh::{Mesh, MeshData};
+use crate::vertex::{VertexLayout, AttributeType};
+use super::normals::{compute_normals, NormalSettings};
+use super::tangents::compute_tangents;
 pub fn gen_lathe_mesh(points: Vec<Vector2f>, segments: i32) -> Mesh {
 
     let width_components: usize = segments as usize;
@@ -27,9 +29,10 @@ pub fn gen_lathe_mesh(points: Vec<Vector2f>, segments: i32) -> Mesh {
             vertices.push(point.y);
             vertices.push(point.x * radians.sin());
 
+            // filled in from the faces below
+            vertices.push(0f32);
             vertices.push(0f32);
             vertices.push(0f32);
-            vertices.push(1f32);
 
             vertices.push(j as f32 / rot_distance as f32);
             vertices.push(i as f32 / height_distance as f32);
@@ -50,15 +53,19 @@ pub fn gen_lathe_mesh(points: Vec<Vector2f>, segments: i32) -> Mesh {
             let third_index =  i3.1 * num_vertices_hori + i3.0;
             let fourth_index =  i4.1 * num_vertices_hori + i4.0;
 
+            // counter clockwise seen from outside for a profile going up, normals come from the winding
             indices.push(first_index as i32);
-            indices.push(second_index as i32);
             indices.push(third_index as i32);
+            indices.push(second_index as i32);
 
             indices.push(third_index as i32);
             indices.push(fourth_index as i32);
             indices.push(second_index as i32);
         }
     }
-    let vertex = Vertex::new(vertices, indices, vertex_layout);
-    Mesh::new(vertex)
+    // sharp corners in the profile stay hard, everything around the axis is smooth
+    let mut data = MeshData::new(vertices, indices, vertex_layout);
+    compute_normals(&mut data, &NormalSettings::default()).unwrap();
+    compute_tangents(&mut data).unwrap();
+    Mesh::new(data)
 }
\ No newline at end of file
@@ -1,4 +1,19 @@
 pub mod quad;
 pub mod lathe;
 pub mod sphere;
-pub mod screen_quad;
\ No newline at end of file
+pub mod screen_quad;
+pub mod attributes;
+pub mod normals;
+pub mod tangents;
+pub mod builder;
+pub mod cuboid;
+pub mod plane;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod capsule;
+pub mod icosphere;
+pub mod triangulate;
+pub mod extrude;
+pub mod sweep;
+pub mod simplify;
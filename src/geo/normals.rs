@@ -0,0 +1,239 @@
+/*
+vertex normals from the triangles around each vertex
+faces meeting at more than the crease angle don't smooth into each other, vertices on such an edge are split so it stays hard
+neighbours are found by position, so uv seams still smooth across
+*/
+
+use std::f32::consts::PI;
+
+use crate::math::Vector3f;
+use crate::mesh::MeshData;
+use crate::vertex::{AttributeType, PrimitiveMode};
+use super::attributes::{self, Error, POSITION, NORMAL};
+
+// 60 degrees, cube edges stay hard and a 16 sided cylinder is smooth
+pub const DEFAULT_CREASE_ANGLE: f32 = PI / 3f32;
+
+// twice the area below which a triangle has no direction
+const DEGENERATE_AREA: f32 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalWeighting {
+    // every face counts the same
+    Uniform,
+    // larger faces pull harder
+    Area,
+    // by the angle of the face's corner, doesn't change with how the faces are split up
+    Angle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NormalSettings {
+    pub weighting: NormalWeighting,
+    // radians, PI smooths everything
+    pub crease_angle: f32,
+}
+
+impl NormalSettings {
+    pub fn smooth() -> Self {
+        NormalSettings {
+            weighting: NormalWeighting::Angle,
+            crease_angle: PI,
+        }
+    }
+    // every face its own normal
+    pub fn flat() -> Self {
+        NormalSettings {
+            weighting: NormalWeighting::Uniform,
+            crease_angle: 0f32,
+        }
+    }
+}
+
+impl Default for NormalSettings {
+    fn default() -> Self {
+        NormalSettings {
+            weighting: NormalWeighting::Angle,
+            crease_angle: DEFAULT_CREASE_ANGLE,
+        }
+    }
+}
+
+// radians between the two edges leaving corner
+pub fn corner_angle(corner: &Vector3f, a: &Vector3f, b: &Vector3f) -> f32 {
+    let mut to_a = Vector3f::sub(a, corner);
+    let mut to_b = Vector3f::sub(b, corner);
+    if to_a.is_zero() || to_b.is_zero() {
+        return 0f32;
+    }
+    to_a.normalize();
+    to_b.normalize();
+    Vector3f::dot(&to_a, &to_b).clamp(-1f32, 1f32).acos()
+}
+
+// overwrites the normals, adds a Normal3D attribute right after the position when the layout has none
+pub fn compute_normals(data: &mut MeshData, settings: &NormalSettings) -> Result<(), Error> {
+    let position = attributes::require(&data.vertex_layout, &POSITION, "position")?;
+    let mut triangles = data.triangle_indices();
+    triangles.truncate(triangles.len() / 3 * 3);
+    if triangles.is_empty() {
+        return Err(Error::NotTriangles);
+    }
+    let normal = match data.vertex_layout.component_offset(&NORMAL) {
+        Some(offset) => offset,
+        None => {
+            // location 1, where vert_common.glsl reads aNormal
+            let after_position = data.vertex_layout.types().iter().position(|t| POSITION.contains(t)).unwrap() + 1;
+            attributes::add_attribute(data, AttributeType::Normal3D, after_position)
+        },
+    };
+
+    let num_faces = triangles.len() / 3;
+    // unit normal per face, None for degenerate ones, and a weight per corner
+    let mut face_normals: Vec<Option<Vector3f>> = Vec::with_capacity(num_faces);
+    let mut corner_weights: Vec<f32> = Vec::with_capacity(triangles.len());
+    for face in 0..num_faces {
+        let p: Vec<Vector3f> = triangles[face * 3..face * 3 + 3].iter()
+            .map(|i| attributes::read_vec3(data, *i as usize, position))
+            .collect();
+        let mut n = Vector3f::cross(&Vector3f::sub(&p[1], &p[0]), &Vector3f::sub(&p[2], &p[0]));
+        let double_area = n.mag();
+        if double_area <= DEGENERATE_AREA {
+            face_normals.push(None);
+            corner_weights.extend_from_slice(&[0f32; 3]);
+            continue;
+        }
+        n.scalar(1f32 / double_area);
+        face_normals.push(Some(n));
+        for k in 0..3 {
+            corner_weights.push(match settings.weighting {
+                NormalWeighting::Uniform => 1f32,
+                NormalWeighting::Area => double_area,
+                NormalWeighting::Angle => corner_angle(&p[k], &p[(k + 1) % 3], &p[(k + 2) % 3]),
+            });
+        }
+    }
+
+    // corners touching each position
+    let groups = attributes::position_groups(data, position);
+    let num_groups = groups.iter().max().map_or(0, |max| max + 1);
+    let mut group_corners: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+    for (corner, vertex) in triangles.iter().enumerate() {
+        group_corners[groups[*vertex as usize]].push(corner);
+    }
+
+    // a corner averages the faces at its position within the crease angle of its own face
+    let cos_crease = settings.crease_angle.cos();
+    let mut values: Vec<f32> = Vec::with_capacity(triangles.len() * 3);
+    for (corner, vertex) in triangles.iter().enumerate() {
+        let own = match face_normals[corner / 3] {
+            Some(n) => n,
+            None => {
+                // degenerate faces take whatever their vertex already had
+                let n = attributes::read_vec3(data, *vertex as usize, normal);
+                values.extend_from_slice(&[n.x, n.y, n.z]);
+                continue;
+            },
+        };
+        let mut sum = Vector3f::zero();
+        for other in group_corners[groups[*vertex as usize]].iter() {
+            if let Some(n) = face_normals[other / 3] {
+                if Vector3f::dot(&own, &n) >= cos_crease - 1e-6 {
+                    let mut weighted = n;
+                    weighted.scalar(corner_weights[*other]);
+                    sum.add_to(&weighted);
+                }
+            }
+        }
+        if sum.mag_squared() <= DEGENERATE_AREA {
+            sum = own;
+        }
+        sum.normalize();
+        values.extend_from_slice(&[sum.x, sum.y, sum.z]);
+    }
+
+    attributes::split_corners(data, &triangles, normal, 3, &values);
+    Ok(())
+}
+
+// 3d triangle meshes without a normal attribute or with zero length normals
+pub fn needs_normals(data: &MeshData) -> bool {
+    match data.primitive {
+        PrimitiveMode::Triangles | PrimitiveMode::TriangleStrip => (),
+        PrimitiveMode::Lines | PrimitiveMode::LineStrip | PrimitiveMode::Points => return false,
+    }
+    if data.vertex_layout.component_offset(&POSITION).is_none() {
+        return false;
+    }
+    let normal = match data.vertex_layout.component_offset(&NORMAL) {
+        Some(offset) => offset,
+        None => return true,
+    };
+    let width = data.vertex_layout.get_num_components();
+    (0..data.vertices.len() / width.max(1))
+        .any(|vertex| attributes::read_vec3(data, vertex, normal).mag_squared() < 1e-6)
+}
+
+fn has_any_normal(data: &MeshData, normal: usize) -> bool {
+    let width = data.vertex_layout.get_num_components();
+    (0..data.vertices.len() / width.max(1))
+        .any(|vertex| attributes::read_vec3(data, vertex, normal).mag_squared() >= 1e-6)
+}
+
+/*
+gives vertices with a zero length normal the angle weighted average of the faces using them
+the other vertices keep their authored normals, and nothing is split, so vertex count and indices stay the same
+*/
+fn fill_zero_normals(data: &mut MeshData, position: usize, normal: usize) {
+    let width = data.vertex_layout.get_num_components();
+    let num_vertices = data.vertices.len() / width.max(1);
+    let zero: Vec<bool> = (0..num_vertices)
+        .map(|vertex| attributes::read_vec3(data, vertex, normal).mag_squared() < 1e-6)
+        .collect();
+
+    let mut sums = vec![Vector3f::zero(); num_vertices];
+    let triangles = data.triangle_indices();
+    for corners in triangles.chunks_exact(3) {
+        if !corners.iter().any(|i| zero[*i as usize]) {
+            continue;
+        }
+        let p: Vec<Vector3f> = corners.iter().map(|i| attributes::read_vec3(data, *i as usize, position)).collect();
+        let mut n = Vector3f::cross(&Vector3f::sub(&p[1], &p[0]), &Vector3f::sub(&p[2], &p[0]));
+        let double_area = n.mag();
+        if double_area <= DEGENERATE_AREA {
+            continue;
+        }
+        n.scalar(1f32 / double_area);
+        for k in 0..3 {
+            let mut weighted = n;
+            weighted.scalar(corner_angle(&p[k], &p[(k + 1) % 3], &p[(k + 2) % 3]));
+            sums[corners[k] as usize].add_to(&weighted);
+        }
+    }
+
+    for (vertex, sum) in sums.iter_mut().enumerate() {
+        // vertices only on degenerate faces, or on none, stay zero
+        if !zero[vertex] || sum.mag_squared() <= DEGENERATE_AREA {
+            continue;
+        }
+        sum.normalize();
+        let start = vertex * width + normal;
+        data.vertices[start..start + 3].copy_from_slice(&[sum.x, sum.y, sum.z]);
+    }
+}
+
+/*
+run at load time, leaves meshes that have usable normals alone
+meshes with only some zero normals get just those filled in, only meshes with none at all are regenerated
+*/
+pub fn ensure_normals(data: &mut MeshData) {
+    if !needs_normals(data) || data.triangle_indices().len() < 3 {
+        return;
+    }
+    let position = data.vertex_layout.component_offset(&POSITION).unwrap();
+    match data.vertex_layout.component_offset(&NORMAL) {
+        Some(normal) if has_any_normal(data, normal) => fill_zero_normals(data, position, normal),
+        // nothing authored to keep, positions and triangles are there so nothing left to fail
+        _ => compute_normals(data, &NormalSettings::default()).unwrap(),
+    }
+}
@@ -0,0 +1,17 @@
+/*
+    Plane on xz facing up, centered on the origin
+    unlike quad this one is sized in world units and lies flat, uvs 0 to 1 over the whole plane
+*/
+use crate::math::Vector3f;
+use crate::mesh::Mesh;
+use super::builder::{push_grid, finish};
+
+pub fn gen_plane_mesh(width: f32, depth: f32, width_segments: i32, depth_segments: i32) -> Mesh {
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+    let corner = Vector3f::new(-width * 0.5, 0f32, depth * 0.5);
+    let u_axis = Vector3f::new(width, 0f32, 0f32);
+    let v_axis = Vector3f::new(0f32, 0f32, -depth);
+    push_grid(&mut vertices, &mut indices, &corner, &u_axis, &v_axis, width_segments, depth_segments);
+    finish(vertices, indices)
+}
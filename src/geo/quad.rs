@@ -1,8 +1,9 @@
 /*
     Quad mesh
 */
-use crate::mesh::{Mesh};
-use crate::vertex::{Vertex, VertexLayout, AttributeType};
+use crate::mesh::{Mesh, MeshData};
+use crate::vertex::{VertexLayout, AttributeType};
+use super::tangents::compute_tangents;
 pub fn gen_quad_mesh(width_components: i32, height_components: i32) -> Mesh {
 
     let num_vertices_hori = width_components + 1;
@@ -42,11 +43,12 @@ pub fn gen_quad_mesh(width_components: i32, height_components: i32) -> Mesh {
             indices.push(second_index);
             indices.push(third_index);
 
-            indices.push(third_index);
-            indices.push(fourth_index);
             indices.push(second_index);
+            indices.push(fourth_index);
+            indices.push(third_index);
         }
     }
-    let vertex = Vertex::new(vertices, indices, vertex_layout);
-    Mesh::new(vertex)
+    let mut data = MeshData::new(vertices, indices, vertex_layout);
+    compute_tangents(&mut data).unwrap();
+    Mesh::new(data)
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
-use crate::mesh::Mesh;
-use crate::vertex::{Vertex, VertexLayout, AttributeType};
+use crate::mesh::{Mesh, MeshData};
+use crate::vertex::{VertexLayout, AttributeType};
 
 pub fn gen_screen_quad() -> Mesh {
     let quad_verts = vec![
@@ -13,5 +13,5 @@ pub fn gen_screen_quad() -> Mesh {
         2, 3, 1,
     ];
 
-    Mesh::new(Vertex::new(quad_verts, quad_ind, VertexLayout::new(vec![AttributeType::Position2D, AttributeType::ST])))
+    Mesh::new(MeshData::new(quad_verts, quad_ind, VertexLayout::new(vec![AttributeType::Position2D, AttributeType::ST])))
 }
\ No newline at end of file
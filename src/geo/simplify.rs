@@ -0,0 +1,497 @@
+/*
+mesh simplification with quadric error metrics (Garland and Heckbert)
+edges collapse onto one of their end points, so attributes are never interpolated, and the cheapest collapses go first
+vertices on a border only slide along the border, and vertices on a uv seam only along the seam taking the vertex on
+the other side with them, so holes don't open up and textures don't smear
+*/
+
+use std::collections::HashMap;
+
+use crate::math::Vector3f;
+use crate::mesh::MeshData;
+use crate::vertex::VertexLayout;
+use super::attributes::{self, Error, POSITION, NORMAL};
+
+// border planes weigh this much more than faces so borders barely move
+const BORDER_WEIGHT: f64 = 10f64;
+// cosine of the most a face may turn in one collapse
+const MIN_NORMAL_DOT: f32 = 0.2;
+
+// x^T Q x for x = (p, 1), the upper triangle of the symmetric 4x4
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    xx: f64, xy: f64, xz: f64, xw: f64,
+    yy: f64, yz: f64, yw: f64,
+    zz: f64, zw: f64,
+    ww: f64,
+}
+
+impl Quadric {
+    // squared distance to the plane n.p + d = 0 times weight, n unit length
+    fn plane(n: &Vector3f, d: f32, weight: f64) -> Self {
+        let (a, b, c, d) = (n.x as f64, n.y as f64, n.z as f64, d as f64);
+        Quadric {
+            xx: weight * a * a, xy: weight * a * b, xz: weight * a * c, xw: weight * a * d,
+            yy: weight * b * b, yz: weight * b * c, yw: weight * b * d,
+            zz: weight * c * c, zw: weight * c * d,
+            ww: weight * d * d,
+        }
+    }
+    fn add(&mut self, other: &Quadric) {
+        self.xx += other.xx; self.xy += other.xy; self.xz += other.xz; self.xw += other.xw;
+        self.yy += other.yy; self.yz += other.yz; self.yw += other.yw;
+        self.zz += other.zz; self.zw += other.zw;
+        self.ww += other.ww;
+    }
+    fn error(&self, p: &Vector3f) -> f64 {
+        let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+        let error = self.xx * x * x + 2f64 * self.xy * x * y + 2f64 * self.xz * x * z + 2f64 * self.xw * x
+            + self.yy * y * y + 2f64 * self.yz * y * z + 2f64 * self.yw * y
+            + self.zz * z * z + 2f64 * self.zw * z
+            + self.ww;
+        error.max(0f64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VertexKind {
+    // one set of attributes, faces all around
+    Manifold,
+    Border,
+    // two sets of attributes split along exactly two seam edges
+    Seam,
+    // corners of borders and seams, non-manifold spots, never moved
+    Locked,
+}
+
+// unit normal and twice the area
+fn face_normal(a: &Vector3f, b: &Vector3f, c: &Vector3f) -> (Vector3f, f32) {
+    let mut n = Vector3f::cross(&Vector3f::sub(b, a), &Vector3f::sub(c, a));
+    let double_area = n.mag();
+    if double_area > 0f32 {
+        n.scalar(1f32 / double_area);
+    }
+    (n, double_area)
+}
+
+// (smaller group, larger group) -> faces using the edge
+fn edge_faces(triangles: &[[usize; 3]], groups: &[usize]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (face, t) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (groups[t[k]], groups[t[(k + 1) % 3]]);
+            edges.entry((a.min(b), a.max(b))).or_default().push(face);
+        }
+    }
+    edges
+}
+
+// the vertex of group in face
+fn wedge_of(t: &[usize; 3], groups: &[usize], group: usize) -> Option<usize> {
+    t.iter().copied().find(|w| groups[*w] == group)
+}
+
+// both faces of the edge use different vertices at both ends
+fn is_seam_edge(edge: (usize, usize), faces: &[usize], triangles: &[[usize; 3]], groups: &[usize]) -> bool {
+    if faces.len() != 2 {
+        return false;
+    }
+    let (t0, t1) = (&triangles[faces[0]], &triangles[faces[1]]);
+    wedge_of(t0, groups, edge.0) != wedge_of(t1, groups, edge.0) && wedge_of(t0, groups, edge.1) != wedge_of(t1, groups, edge.1)
+}
+
+fn classify(triangles: &[[usize; 3]], groups: &[usize], edges: &HashMap<(usize, usize), Vec<usize>>, num_groups: usize) -> Vec<VertexKind> {
+    let mut wedges: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+    for t in triangles.iter() {
+        for w in t.iter() {
+            let list = &mut wedges[groups[*w]];
+            if !list.contains(w) {
+                list.push(*w);
+            }
+        }
+    }
+    let mut borders = vec![0; num_groups];
+    let mut seams = vec![0; num_groups];
+    let mut non_manifold = vec![false; num_groups];
+    for (edge, faces) in edges.iter() {
+        let ends = [edge.0, edge.1];
+        if faces.len() == 1 {
+            ends.iter().for_each(|g| borders[*g] += 1);
+        } else if faces.len() > 2 {
+            ends.iter().for_each(|g| non_manifold[*g] = true);
+        } else if is_seam_edge(*edge, faces, triangles, groups) {
+            ends.iter().for_each(|g| seams[*g] += 1);
+        }
+    }
+    (0..num_groups)
+        .map(|g| {
+            if non_manifold[g] {
+                VertexKind::Locked
+            } else if borders[g] > 0 {
+                if borders[g] == 2 && wedges[g].len() == 1 { VertexKind::Border } else { VertexKind::Locked }
+            } else if wedges[g].len() > 1 {
+                if seams[g] == 2 && wedges[g].len() == 2 { VertexKind::Seam } else { VertexKind::Locked }
+            } else {
+                VertexKind::Manifold
+            }
+        })
+        .collect()
+}
+
+// where each vertex of `from` goes, taken from the faces on the edge, None if that's ambiguous
+fn wedge_targets(from: usize, to: usize, triangles: &[[usize; 3]], incident: &[usize], groups: &[usize]) -> Option<HashMap<usize, usize>> {
+    let mut targets: HashMap<usize, usize> = HashMap::new();
+    for face in incident.iter() {
+        let t = &triangles[*face];
+        if let Some(target) = wedge_of(t, groups, to) {
+            let source = wedge_of(t, groups, from).unwrap();
+            if *targets.entry(source).or_insert(target) != target {
+                return None;
+            }
+        }
+    }
+    let complete = incident.iter()
+        .all(|face| targets.contains_key(&wedge_of(&triangles[*face], groups, from).unwrap()));
+    if complete { Some(targets) } else { None }
+}
+
+// the groups sharing a face with group
+fn neighbours(group: usize, triangles: &[[usize; 3]], incident: &[usize], groups: &[usize]) -> Vec<usize> {
+    let mut result = Vec::new();
+    for face in incident.iter() {
+        for w in triangles[*face].iter() {
+            let g = groups[*w];
+            if g != group && !result.contains(&g) {
+                result.push(g);
+            }
+        }
+    }
+    result
+}
+
+/*
+simplified copy with at most target_triangles, or as close as it gets before only collapses that would break a border
+or seam, fold the surface or flip a face are left, vertex attributes are copied unchanged
+*/
+pub fn simplify(data: &MeshData, target_triangles: usize) -> Result<MeshData, Error> {
+    let position = attributes::require(&data.vertex_layout, &POSITION, "position")?;
+    let normal = data.vertex_layout.component_offset(&NORMAL);
+    let groups = attributes::position_groups(data, position);
+    let num_groups = groups.iter().max().map_or(0, |max| max + 1);
+    let mut positions = vec![Vector3f::zero(); num_groups];
+    for (vertex, group) in groups.iter().enumerate() {
+        positions[*group] = attributes::read_vec3(data, vertex, position);
+    }
+
+    let mut triangles: Vec<[usize; 3]> = data.triangle_indices()
+        .chunks_exact(3)
+        .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+        .filter(|t| groups[t[0]] != groups[t[1]] && groups[t[1]] != groups[t[2]] && groups[t[2]] != groups[t[0]])
+        .collect();
+    if triangles.is_empty() {
+        return Err(Error::NotTriangles);
+    }
+
+    // area weighted face planes, plus planes standing on the border edges
+    let mut quadrics = vec![Quadric::default(); num_groups];
+    for t in triangles.iter() {
+        let p = [positions[groups[t[0]]], positions[groups[t[1]]], positions[groups[t[2]]]];
+        let (n, double_area) = face_normal(&p[0], &p[1], &p[2]);
+        if double_area > 0f32 {
+            let quadric = Quadric::plane(&n, -Vector3f::dot(&n, &p[0]), 0.5 * double_area as f64);
+            t.iter().for_each(|w| quadrics[groups[*w]].add(&quadric));
+        }
+    }
+    for ((a, b), faces) in edge_faces(&triangles, &groups).iter() {
+        if faces.len() != 1 {
+            continue;
+        }
+        let t = &triangles[faces[0]];
+        let (n, _) = face_normal(&positions[groups[t[0]]], &positions[groups[t[1]]], &positions[groups[t[2]]]);
+        let edge = Vector3f::sub(&positions[*b], &positions[*a]);
+        let mut m = Vector3f::cross(&edge, &n);
+        if m.is_zero() {
+            continue;
+        }
+        m.normalize();
+        let quadric = Quadric::plane(&m, -Vector3f::dot(&m, &positions[*a]), BORDER_WEIGHT * edge.mag_squared() as f64);
+        quadrics[*a].add(&quadric);
+        quadrics[*b].add(&quadric);
+    }
+
+    // passes of independent collapses, a collapse locks everything around it until the next pass
+    while triangles.len() > target_triangles {
+        let edges = edge_faces(&triangles, &groups);
+        let kinds = classify(&triangles, &groups, &edges, num_groups);
+        let mut incident: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+        for (face, t) in triangles.iter().enumerate() {
+            t.iter().for_each(|w| incident[groups[*w]].push(face));
+        }
+
+        let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+        for (edge, faces) in edges.iter() {
+            if faces.len() > 2 {
+                continue;
+            }
+            let border = faces.len() == 1;
+            let seam = is_seam_edge(*edge, faces, &triangles, &groups);
+            for (from, to) in [(edge.0, edge.1), (edge.1, edge.0)].iter().copied() {
+                let allowed = match kinds[from] {
+                    VertexKind::Manifold => true,
+                    VertexKind::Border => border,
+                    VertexKind::Seam => seam,
+                    VertexKind::Locked => false,
+                };
+                if allowed {
+                    let mut quadric = quadrics[from];
+                    quadric.add(&quadrics[to]);
+                    candidates.push((quadric.error(&positions[to]), from, to));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut touched = vec![false; num_groups];
+        let mut dead = vec![false; triangles.len()];
+        let mut remaining = triangles.len();
+        let mut collapsed = 0;
+        for (_, from, to) in candidates.into_iter() {
+            if remaining <= target_triangles {
+                break;
+            }
+            if touched[from] || touched[to] {
+                continue;
+            }
+            let targets = match wedge_targets(from, to, &triangles, &incident[from], &groups) {
+                Some(targets) => targets,
+                None => continue,
+            };
+
+            // the ends may only share the vertices opposite the edge, anything more folds the surface
+            let edge_face_count = incident[from].iter().filter(|face| wedge_of(&triangles[**face], &groups, to).is_some()).count();
+            let from_neighbours = neighbours(from, &triangles, &incident[from], &groups);
+            let to_neighbours = neighbours(to, &triangles, &incident[to], &groups);
+            let shared = from_neighbours.iter().filter(|g| **g != to && to_neighbours.contains(g)).count();
+            if shared != edge_face_count {
+                continue;
+            }
+
+            let flips = incident[from].iter()
+                .filter(|face| wedge_of(&triangles[**face], &groups, to).is_none())
+                .any(|face| {
+                    let t = &triangles[*face];
+                    let before: Vec<Vector3f> = t.iter().map(|w| positions[groups[*w]]).collect();
+                    let after: Vec<Vector3f> = t.iter().map(|w| if groups[*w] == from { positions[to] } else { positions[groups[*w]] }).collect();
+                    let (n_before, _) = face_normal(&before[0], &before[1], &before[2]);
+                    let (n_after, double_area) = face_normal(&after[0], &after[1], &after[2]);
+                    // the turns add up over collapses, so also keep the face on the side its vertex normals shade
+                    let against_normals = normal.is_some_and(|offset| {
+                        let mut shading = Vector3f::zero();
+                        t.iter()
+                            .map(|w| if groups[*w] == from { targets[w] } else { *w })
+                            .for_each(|w| shading.add_to(&attributes::read_vec3(data, w, offset)));
+                        Vector3f::dot(&shading, &n_after) <= 0f32
+                    });
+                    double_area <= 0f32 || Vector3f::dot(&n_before, &n_after) < MIN_NORMAL_DOT || against_normals
+                });
+            if flips {
+                continue;
+            }
+
+            touched[to] = true;
+            from_neighbours.iter().for_each(|g| touched[*g] = true);
+            for face in incident[from].iter() {
+                if wedge_of(&triangles[*face], &groups, to).is_some() {
+                    dead[*face] = true;
+                    remaining -= 1;
+                } else {
+                    for w in triangles[*face].iter_mut() {
+                        if groups[*w] == from {
+                            *w = targets[&*w];
+                        }
+                    }
+                }
+            }
+            touched[from] = true;
+            let merged = quadrics[from];
+            quadrics[to].add(&merged);
+            collapsed += 1;
+        }
+        if collapsed == 0 {
+            break;
+        }
+        triangles = triangles.into_iter()
+            .zip(dead)
+            .filter(|(_, dead)| !dead)
+            .map(|(t, _)| t)
+            .collect();
+    }
+
+    // only the vertices still in use, in order of first use
+    let width = data.vertex_layout.get_num_components();
+    let mut remap: HashMap<usize, i32> = HashMap::new();
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::with_capacity(triangles.len() * 3);
+    for w in triangles.iter().flat_map(|t| t.iter()) {
+        let index = *remap.entry(*w).or_insert_with(|| {
+            vertices.extend_from_slice(&data.vertices[w * width..(w + 1) * width]);
+            (vertices.len() / width - 1) as i32
+        });
+        indices.push(index);
+    }
+    let vertex_layout = VertexLayout::new(data.vertex_layout.types().to_vec());
+    Ok(MeshData::new(vertices, indices, vertex_layout).with_usage(data.usage))
+}
+
+// each level simplified from the one before, ratios are of the original triangle count
+pub fn build_lod_chain(data: &MeshData, ratios: &[f32]) -> Result<Vec<MeshData>, Error> {
+    let triangles = data.triangle_indices().len() / 3;
+    let mut lods: Vec<MeshData> = Vec::with_capacity(ratios.len());
+    for ratio in ratios.iter() {
+        let target = (triangles as f32 * ratio.clamp(0f32, 1f32)) as usize;
+        let lod = simplify(lods.last().unwrap_or(data), target)?;
+        lods.push(lod);
+    }
+    Ok(lods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::icosphere::gen_icosphere_mesh;
+    use crate::geo::plane::gen_plane_mesh;
+    use crate::geo::attributes::TEX_COORD;
+
+    fn triangles(data: &MeshData) -> Vec<[usize; 3]> {
+        data.triangle_indices().chunks_exact(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]).collect()
+    }
+
+    // faces per edge, with vertices at the same position counting as one
+    fn position_edges(data: &MeshData) -> HashMap<(usize, usize), Vec<usize>> {
+        let position = data.vertex_layout.component_offset(&POSITION).unwrap();
+        edge_faces(&triangles(data), &attributes::position_groups(data, position))
+    }
+
+    fn key(data: &MeshData, vertex: usize, offset: usize, width: usize) -> Vec<u32> {
+        data.vertices[vertex * data.vertex_layout.get_num_components() + offset..][..width].iter().map(|v| v.to_bits()).collect()
+    }
+
+    #[test]
+    fn closed_sphere_stays_closed() {
+        let sphere = gen_icosphere_mesh(1f32, 3);
+        assert!(position_edges(sphere.data()).values().all(|faces| faces.len() == 2));
+        for target in [640, 160, 40].iter() {
+            let simplified = simplify(sphere.data(), *target).unwrap();
+            assert!(triangles(&simplified).len() < 1280);
+            for (edge, faces) in position_edges(&simplified).iter() {
+                assert_eq!(faces.len(), 2, "edge {:?} has {} faces at {} triangles", edge, faces.len(), target);
+            }
+        }
+    }
+
+    #[test]
+    fn classifies_grid_vertices() {
+        let plane = gen_plane_mesh(2f32, 2f32, 8, 8);
+        let data = plane.data();
+        let position = data.vertex_layout.component_offset(&POSITION).unwrap();
+        let groups = attributes::position_groups(data, position);
+        let num_groups = groups.iter().max().unwrap() + 1;
+        let triangles = triangles(data);
+        let kinds = classify(&triangles, &groups, &edge_faces(&triangles, &groups), num_groups);
+        for (vertex, group) in groups.iter().enumerate() {
+            let p = attributes::read_vec3(data, vertex, position);
+            let expected = if p.x.abs() > 0.999 || p.z.abs() > 0.999 { VertexKind::Border } else { VertexKind::Manifold };
+            assert_eq!(kinds[*group], expected, "vertex at {:?}", p);
+        }
+    }
+
+    // border vertices may only slide along the border, so the outline and the corners stay where they were
+    #[test]
+    fn grid_keeps_its_border() {
+        let plane = gen_plane_mesh(2f32, 2f32, 8, 8);
+        let simplified = simplify(plane.data(), 2).unwrap();
+        assert!(triangles(&simplified).len() < 128);
+
+        let position = simplified.vertex_layout.component_offset(&POSITION).unwrap();
+        let groups = attributes::position_groups(&simplified, position);
+        let mut group_positions = vec![Vector3f::zero(); groups.len()];
+        for (vertex, group) in groups.iter().enumerate() {
+            group_positions[*group] = attributes::read_vec3(&simplified, vertex, position);
+        }
+        let on_border = |p: &Vector3f| (p.x.abs() - 1f32).abs() < 1e-5 || (p.z.abs() - 1f32).abs() < 1e-5;
+
+        let mut border_length = 0f32;
+        for ((a, b), faces) in position_edges(&simplified).iter() {
+            let (a, b) = (group_positions[*a], group_positions[*b]);
+            if faces.len() == 1 {
+                assert!(on_border(&a) && on_border(&b), "border edge {:?} {:?} moved inside", a, b);
+                border_length += Vector3f::sub(&b, &a).mag();
+            }
+        }
+        assert!((border_length - 8f32).abs() < 1e-4, "border is {} long", border_length);
+        for corner in [(-1f32, -1f32), (-1f32, 1f32), (1f32, -1f32), (1f32, 1f32)].iter() {
+            assert!(group_positions.iter().any(|p| (p.x - corner.0).abs() < 1e-5 && (p.z - corner.1).abs() < 1e-5), "corner {:?} was collapsed", corner);
+        }
+    }
+
+    #[test]
+    fn seams_stay_together() {
+        let sphere = gen_icosphere_mesh(1f32, 3);
+        let data = sphere.data();
+        let position = data.vertex_layout.component_offset(&POSITION).unwrap();
+        let tex_coord = data.vertex_layout.component_offset(&TEX_COORD).unwrap();
+        let simplified = simplify(data, 20).unwrap();
+
+        // the uvs at each position, before and after
+        let uvs_by_position = |data: &MeshData| {
+            let mut map: HashMap<Vec<u32>, Vec<Vec<u32>>> = HashMap::new();
+            for t in triangles(data).iter() {
+                for v in t.iter() {
+                    let uvs = map.entry(key(data, *v, position, 3)).or_default();
+                    let uv = key(data, *v, tex_coord, 2);
+                    if !uvs.contains(&uv) {
+                        uvs.push(uv);
+                    }
+                }
+            }
+            map
+        };
+        let before = uvs_by_position(data);
+        let after = uvs_by_position(&simplified);
+
+        // vertices split only by uv away from the poles slide along the seam or stay put
+        let groups = attributes::position_groups(data, position);
+        let num_groups = groups.iter().max().unwrap() + 1;
+        let triangles = triangles(data);
+        let kinds = classify(&triangles, &groups, &edge_faces(&triangles, &groups), num_groups);
+        for (vertex, group) in groups.iter().enumerate() {
+            let p = attributes::read_vec3(data, vertex, position);
+            if before[&key(data, vertex, position, 3)].len() == 2 {
+                assert!(kinds[*group] == VertexKind::Seam || kinds[*group] == VertexKind::Locked, "seam vertex at {:?} is {:?}", p, kinds[*group]);
+            }
+        }
+        let mut seam_pairs = 0;
+        for (position, uvs) in after.iter() {
+            let original = &before[position];
+            if original.len() == 2 {
+                // both sides of the seam are still there, at the same position
+                assert_eq!(uvs.len(), 2, "seam at {:?} lost a side", position);
+                seam_pairs += 1;
+            }
+        }
+        assert!(seam_pairs > 0);
+    }
+
+    #[test]
+    fn lod_chain_hits_targets() {
+        let sphere = gen_icosphere_mesh(1f32, 3);
+        let ratios = [0.5f32, 0.25f32, 0.1f32];
+        let lods = build_lod_chain(sphere.data(), &ratios).unwrap();
+        assert_eq!(lods.len(), ratios.len());
+        for (lod, ratio) in lods.iter().zip(ratios.iter()) {
+            let target = (1280f32 * ratio) as usize;
+            let count = triangles(lod).len();
+            assert!(count <= target && count * 10 >= target * 9, "{} triangles for a target of {}", count, target);
+        }
+    }
+}
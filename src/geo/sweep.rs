@@ -0,0 +1,135 @@
+/*
+    A 2D profile swept along a 3D path, for trims, railings and pipes
+    profile x goes to the right of the path and y up, the frame is carried along the path without twisting
+    joints are mitered so the profile keeps its size through turns, every face is flat so edges stay hard
+    uvs are in world units, u along the path and v around the profile
+*/
+use crate::math::{Vector2f, Vector3f};
+use crate::mesh::Mesh;
+use super::builder::{push_quad, push_vertex, num_vertices, finish};
+use super::triangulate::{triangulate, signed_area};
+
+// path points closer than this are merged
+const MIN_SEGMENT: f32 = 1e-5;
+
+fn unit(mut v: Vector3f) -> Vector3f {
+    v.normalize();
+    v
+}
+
+fn times(v: &Vector3f, r: f32) -> Vector3f {
+    Vector3f::new(v.x * r, v.y * r, v.z * r)
+}
+
+// mirrored on the plane through the origin with unit normal n
+fn reflect(v: &Vector3f, n: &Vector3f) -> Vector3f {
+    Vector3f::sub(v, &times(n, 2f32 * Vector3f::dot(v, n)))
+}
+
+/*
+up vector per segment, starting as close to world up as the first segment allows
+mirroring on the miter plane of a joint turns it exactly like the path turns, so the profile never twists
+*/
+fn segment_ups(directions: &[Vector3f]) -> Vec<Vector3f> {
+    let first = directions[0];
+    let world_up = if first.y.abs() < 0.99 { Vector3f::new(0f32, 1f32, 0f32) } else { Vector3f::new(0f32, 0f32, -1f32) };
+    let mut ups = Vec::with_capacity(directions.len());
+    ups.push(unit(Vector3f::sub(&world_up, &times(&first, Vector3f::dot(&world_up, &first)))));
+    for k in 1..directions.len() {
+        let bisector = Vector3f::add(&directions[k - 1], &directions[k]);
+        let up = if bisector.mag_squared() > 1e-8 { reflect(&ups[k - 1], &unit(bisector)) } else { ups[k - 1] };
+        // square to the segment again against drift
+        ups.push(unit(Vector3f::sub(&up, &times(&directions[k], Vector3f::dot(&up, &directions[k])))));
+    }
+    ups
+}
+
+// open profiles are single sided and face right of their direction, like a counter clockwise closed one
+pub fn gen_sweep_mesh(profile: &[Vector2f], closed: bool, path: &[Vector3f], caps: bool) -> Mesh {
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<i32> = Vec::new();
+
+    let mut points: Vec<Vector3f> = Vec::with_capacity(path.len());
+    for point in path.iter() {
+        if points.last().is_none_or(|last| Vector3f::sub(point, last).mag() > MIN_SEGMENT) {
+            points.push(*point);
+        }
+    }
+    if points.len() < 2 || profile.len() < 2 {
+        return finish(vertices, indices);
+    }
+    let mut profile = profile.to_vec();
+    if closed && signed_area(&profile) < 0f32 {
+        profile.reverse();
+    }
+
+    let directions: Vec<Vector3f> = points.windows(2).map(|w| unit(Vector3f::sub(&w[1], &w[0]))).collect();
+    let ups = segment_ups(&directions);
+
+    // profile of segment k placed at a joint, with how far each point moved along the segment for the miter
+    let ring = |k: usize, joint: usize| -> Vec<(Vector3f, f32)> {
+        let direction = directions[k];
+        let side = Vector3f::cross(&direction, &ups[k]);
+        let miter = if joint > 0 && joint < points.len() - 1 {
+            let bisector = Vector3f::add(&directions[joint - 1], &directions[joint]);
+            if bisector.mag_squared() > 1e-8 { Some(unit(bisector)) } else { None }
+        } else {
+            None
+        };
+        profile.iter()
+            .map(|p| {
+                let offset = Vector3f::add(&times(&side, p.x), &times(&ups[k], p.y));
+                let shift = miter.map_or(0f32, |m| -Vector3f::dot(&offset, &m) / Vector3f::dot(&direction, &m));
+                (Vector3f::add(&Vector3f::add(&points[joint], &offset), &times(&direction, shift)), shift)
+            })
+            .collect()
+    };
+
+    // v at every profile point, the closing edge ends at the full length
+    let num_edges = if closed { profile.len() } else { profile.len() - 1 };
+    let mut profile_v = vec![0f32];
+    for i in 0..num_edges {
+        let (a, b) = (profile[i], profile[(i + 1) % profile.len()]);
+        let length = Vector2f::new(b.x - a.x, b.y - a.y).mag();
+        profile_v.push(profile_v[i] + length);
+    }
+
+    let mut distance = 0f32;
+    for k in 0..directions.len() {
+        let start = ring(k, k);
+        let end = ring(k, k + 1);
+        let length = Vector3f::sub(&points[k + 1], &points[k]).mag();
+        for i in 0..num_edges {
+            let j = (i + 1) % profile.len();
+            let corners = [start[i].0, end[i].0, end[j].0, start[j].0];
+            let uvs = [
+                (distance + start[i].1, profile_v[i]),
+                (distance + length + end[i].1, profile_v[i]),
+                (distance + length + end[j].1, profile_v[i + 1]),
+                (distance + start[j].1, profile_v[i + 1]),
+            ];
+            push_quad(&mut vertices, &mut indices, corners, uvs);
+        }
+        distance += length;
+    }
+
+    if closed && caps && profile.len() >= 3 {
+        let triangles = triangulate(&profile);
+        let last = directions.len() - 1;
+        // a counter clockwise profile looks clockwise from ahead, so the start cap keeps the winding
+        for (cap_ring, direction, reverse) in [(ring(0, 0), times(&directions[0], -1f32), false), (ring(last, last + 1), directions[last], true)].iter() {
+            let first = num_vertices(&vertices);
+            for (point, corner) in profile.iter().zip(cap_ring.iter()) {
+                push_vertex(&mut vertices, &corner.0, direction, point.x, point.y);
+            }
+            for [a, b, c] in triangles.iter().copied() {
+                if *reverse {
+                    indices.extend_from_slice(&[first + a as i32, first + c as i32, first + b as i32]);
+                } else {
+                    indices.extend_from_slice(&[first + a as i32, first + b as i32, first + c as i32]);
+                }
+            }
+        }
+    }
+    finish(vertices, indices)
+}
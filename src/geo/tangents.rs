@@ -0,0 +1,138 @@
+/*
+per vertex tangents for normal mapping, in the MikkTSpace convention bakers use:
+xyz is the tangent along +u orthogonalized against the vertex normal, w the handedness,
+and the shader rebuilds the bitangent as w * cross(normal, tangent)
+
+triangles are angle weighted and mirrored uv islands get their own vertices so w never interpolates across a mirror seam
+this isn't a port of the reference implementation, it skips its vertex welding, so maps baked
+against very low poly meshes can show small differences at hard edges
+*/
+
+use std::collections::HashMap;
+
+use crate::math::Vector3f;
+use crate::mesh::MeshData;
+use crate::vertex::{AttributeType, PrimitiveMode};
+use super::attributes::{self, Error, POSITION, NORMAL, TEX_COORD, TANGENT};
+use super::normals::corner_angle;
+
+// uv area below which a triangle says nothing about the tangent direction
+const DEGENERATE_UV_AREA: f32 = 1e-12;
+
+// any unit vector perpendicular to n
+fn perpendicular(n: &Vector3f) -> Vector3f {
+    let axis = if n.x.abs() < 0.9 { Vector3f::new(1f32, 0f32, 0f32) } else { Vector3f::new(0f32, 1f32, 0f32) };
+    let mut t = Vector3f::cross(n, &axis);
+    t.normalize();
+    t
+}
+
+// overwrites the tangents, adds a Tangent4D attribute when the layout has none, needs normals and uvs
+pub fn compute_tangents(data: &mut MeshData) -> Result<(), Error> {
+    let position = attributes::require(&data.vertex_layout, &POSITION, "position")?;
+    let normal = attributes::require(&data.vertex_layout, &NORMAL, "normal")?;
+    let tex_coord = attributes::require(&data.vertex_layout, &TEX_COORD, "tex coord")?;
+    let mut triangles = data.triangle_indices();
+    triangles.truncate(triangles.len() / 3 * 3);
+    if triangles.is_empty() {
+        return Err(Error::NotTriangles);
+    }
+    let tangent = match data.vertex_layout.component_offset(&TANGENT) {
+        Some(offset) => offset,
+        None => {
+            let last = data.vertex_layout.types().len();
+            attributes::add_attribute(data, AttributeType::Tangent4D, last)
+        },
+    };
+
+    // face tangent and handedness, None where the uvs are degenerate
+    let num_faces = triangles.len() / 3;
+    let mut face_tangents: Vec<Option<(Vector3f, f32)>> = Vec::with_capacity(num_faces);
+    for face in 0..num_faces {
+        let corners = &triangles[face * 3..face * 3 + 3];
+        let p: Vec<Vector3f> = corners.iter().map(|i| attributes::read_vec3(data, *i as usize, position)).collect();
+        let uv: Vec<(f32, f32)> = corners.iter().map(|i| attributes::read_vec2(data, *i as usize, tex_coord)).collect();
+
+        let e1 = Vector3f::sub(&p[1], &p[0]);
+        let e2 = Vector3f::sub(&p[2], &p[0]);
+        let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+        let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() <= DEGENERATE_UV_AREA {
+            face_tangents.push(None);
+            continue;
+        }
+        let r = 1f32 / det;
+        let t = Vector3f::new((e1.x * dv2 - e2.x * dv1) * r, (e1.y * dv2 - e2.y * dv1) * r, (e1.z * dv2 - e2.z * dv1) * r);
+        let b = Vector3f::new((e2.x * du1 - e1.x * du2) * r, (e2.y * du1 - e1.y * du2) * r, (e2.z * du1 - e1.z * du2) * r);
+        let n = Vector3f::cross(&e1, &e2);
+        let sign = if Vector3f::dot(&Vector3f::cross(&n, &t), &b) < 0f32 { -1f32 } else { 1f32 };
+        face_tangents.push(Some((t, sign)));
+    }
+
+    // summed per vertex and handedness, each corner projected onto its vertex normal first
+    let mut sums: HashMap<(i32, bool), Vector3f> = HashMap::new();
+    for (corner, vertex) in triangles.iter().enumerate() {
+        if let Some((t, sign)) = face_tangents[corner / 3] {
+            let face = corner / 3 * 3;
+            let k = corner - face;
+            let p: Vec<Vector3f> = (0..3).map(|j| attributes::read_vec3(data, triangles[face + (k + j) % 3] as usize, position)).collect();
+            let n = attributes::read_vec3(data, *vertex as usize, normal);
+
+            let mut along = n;
+            along.scalar(Vector3f::dot(&n, &t));
+            let mut projected = Vector3f::sub(&t, &along);
+            if projected.is_zero() {
+                continue;
+            }
+            projected.normalize();
+            projected.scalar(corner_angle(&p[0], &p[1], &p[2]));
+            sums.entry((*vertex, sign > 0f32)).or_insert_with(Vector3f::zero).add_to(&projected);
+        }
+    }
+
+    let mut values: Vec<f32> = Vec::with_capacity(triangles.len() * 4);
+    for (corner, vertex) in triangles.iter().enumerate() {
+        let mut n = attributes::read_vec3(data, *vertex as usize, normal);
+        if n.is_zero() {
+            n = Vector3f::new(0f32, 0f32, 1f32);
+        }
+        n.normalize();
+        let sign = face_tangents[corner / 3].map_or(1f32, |(_, sign)| sign);
+
+        // normals from files aren't always unit length, project once more against the normalized one
+        let mut t = sums.get(&(*vertex, sign > 0f32)).copied().unwrap_or_else(Vector3f::zero);
+        let mut along = n;
+        along.scalar(Vector3f::dot(&n, &t));
+        t = Vector3f::sub(&t, &along);
+        if t.mag_squared() <= DEGENERATE_UV_AREA {
+            t = perpendicular(&n);
+        } else {
+            t.normalize();
+        }
+        values.extend_from_slice(&[t.x, t.y, t.z, sign]);
+    }
+
+    attributes::split_corners(data, &triangles, tangent, 4, &values);
+    Ok(())
+}
+
+// 3d triangle meshes with normals and uvs but without a tangent attribute
+pub fn needs_tangents(data: &MeshData) -> bool {
+    match data.primitive {
+        PrimitiveMode::Triangles | PrimitiveMode::TriangleStrip => (),
+        PrimitiveMode::Lines | PrimitiveMode::LineStrip | PrimitiveMode::Points => return false,
+    }
+    let layout = &data.vertex_layout;
+    layout.component_offset(&TANGENT).is_none()
+        && layout.component_offset(&POSITION).is_some()
+        && layout.component_offset(&NORMAL).is_some()
+        && layout.component_offset(&TEX_COORD).is_some()
+}
+
+// run at load time after ensure_normals, meshes without uvs can't have tangents and are left alone
+pub fn ensure_tangents(data: &mut MeshData) {
+    if needs_tangents(data) && data.triangle_indices().len() >= 3 {
+        compute_tangents(data).unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+/*
+    Torus around y, major radius to the middle of the tube
+    u goes around y, v around the tube starting on the outside
+*/
+use std::f32::consts::PI;
+
+use crate::math::Vector3f;
+use crate::mesh::Mesh;
+use super::builder::{push_vertex, push_grid_indices, around_y, finish, VERTEX_WIDTH};
+
+pub fn gen_torus_mesh(major_radius: f32, minor_radius: f32, major_segments: i32, minor_segments: i32) -> Mesh {
+    let major_segments = major_segments.max(3);
+    let minor_segments = minor_segments.max(3);
+    let mut vertices: Vec<f32> = Vec::with_capacity(VERTEX_WIDTH * ((major_segments + 1) * (minor_segments + 1)) as usize);
+    let mut indices: Vec<i32> = Vec::with_capacity(6 * (major_segments * minor_segments) as usize);
+
+    // rows around the tube, columns around y, both seams doubled
+    for i in 0..=minor_segments {
+        let v = i as f32 / minor_segments as f32;
+        let tube_angle = v * 2f32 * PI;
+        for j in 0..=major_segments {
+            let u = j as f32 / major_segments as f32;
+            let angle = u * 2f32 * PI;
+            let normal = around_y(tube_angle.cos(), tube_angle.sin(), angle);
+            let mut position = around_y(major_radius, 0f32, angle);
+            position.add_to(&Vector3f::new(normal.x * minor_radius, normal.y * minor_radius, normal.z * minor_radius));
+            push_vertex(&mut vertices, &position, &normal, u, v);
+        }
+    }
+    push_grid_indices(&mut indices, 0, major_segments, minor_segments);
+    finish(vertices, indices)
+}
@@ -0,0 +1,94 @@
+/*
+ear clipping for the caps of extruded and swept outlines
+simple polygons only, holes and self intersections aren't handled, O(n^2) which is fine for outlines
+*/
+
+use crate::math::Vector2f;
+
+// positive when counter clockwise
+pub fn signed_area(polygon: &[Vector2f]) -> f32 {
+    let mut area = 0f32;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    0.5 * area
+}
+
+fn cross(a: &Vector2f, b: &Vector2f, c: &Vector2f) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn inside_triangle(p: &Vector2f, a: &Vector2f, b: &Vector2f, c: &Vector2f) -> bool {
+    cross(a, b, p) >= 0f32 && cross(b, c, p) >= 0f32 && cross(c, a, p) >= 0f32
+}
+
+// triangles as indices into polygon, always counter clockwise whichever way the polygon goes
+pub fn triangulate(polygon: &[Vector2f]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    if signed_area(polygon) < 0f32 {
+        remaining.reverse();
+    }
+    let mut triangles = Vec::with_capacity(polygon.len().saturating_sub(2));
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (&polygon[a], &polygon[b], &polygon[c]);
+            // reflex or flat corners are never ears
+            if cross(pa, pb, pc) <= 0f32 {
+                return false;
+            }
+            !remaining.iter()
+                .filter(|j| **j != a && **j != b && **j != c)
+                .any(|j| inside_triangle(&polygon[*j], pa, pb, pc))
+        };
+        // with only flat or broken corners left clip anyway, so bad outlines still terminate
+        let ear = (0..n).find(|i| is_ear(*i)).unwrap_or(0);
+        triangles.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+        remaining.remove(ear);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // concave at (1, 1)
+    fn l_outline() -> Vec<Vector2f> {
+        [(0f32, 0f32), (2f32, 0f32), (2f32, 1f32), (1f32, 1f32), (1f32, 2f32), (0f32, 2f32)].iter()
+            .map(|(x, y)| Vector2f::new(*x, *y))
+            .collect()
+    }
+
+    fn check(polygon: &[Vector2f]) {
+        let triangles = triangulate(polygon);
+        assert_eq!(triangles.len(), polygon.len() - 2);
+        let mut total = 0f32;
+        for [a, b, c] in triangles.iter().copied() {
+            let area = signed_area(&[polygon[a], polygon[b], polygon[c]]);
+            assert!(area > 0f32, "triangle {:?} has area {}", [a, b, c], area);
+            total += area;
+        }
+        // nothing outside the outline, nothing left over
+        assert!((total - signed_area(polygon).abs()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn concave_counter_clockwise() {
+        check(&l_outline());
+    }
+
+    #[test]
+    fn concave_clockwise() {
+        let mut outline = l_outline();
+        outline.reverse();
+        check(&outline);
+    }
+}
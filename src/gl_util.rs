@@ -22,4 +22,27 @@ pub fn gl_dump_errors() {
             _ => println!("GL: Unknown error!"),
         }
     }
-}
\ No newline at end of file
+}
+
+// (major, minor) of the current context
+pub fn gl_version() -> (i32, i32) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+// listed by the current context, a loaded function pointer alone doesn't mean the driver supports it
+pub fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count.max(0) as gl::types::GLuint).any(|i| {
+            let extension = gl::GetStringi(gl::EXTENSIONS, i);
+            !extension.is_null() && std::ffi::CStr::from_ptr(extension as *const std::os::raw::c_char).to_bytes() == name.as_bytes()
+        })
+    }
+}
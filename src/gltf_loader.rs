@@ -0,0 +1,388 @@
+/*
+glTF 2.0 import (.gltf and .glb)
+meshes, metallic-roughness materials and the node hierarchy become RenderObjects and Lights
+external buffers and images are read through the loader so they resolve like every other asset
+*/
+
+use gltf::khr_lights_punctual::Kind;
+
+use crate::loader::{self, FilePath};
+use crate::resources::{self, Resources, Handle};
+use crate::mesh::{Mesh, MeshData};
+use crate::shader::Program;
+use crate::vertex::{VertexLayout, AttributeType, PrimitiveMode};
+use crate::texture::{Texture, SamplerDesc, Filter, MipMode, Wrap, MipGeneration, DEFAULT_ANISOTROPY};
+use crate::material::{Material, Albedo, Normal, Metallic, Roughness, AO, MaterialPropertyType, TextureFactors};
+use crate::render_object::{self, RenderObject};
+use crate::light::Light;
+use crate::transform::Transform;
+use crate::math::{Vector3f, Mat4f};
+use crate::memory::References;
+use crate::geo::normals::ensure_normals;
+use crate::geo::tangents::ensure_tangents;
+
+#[derive(Debug)]
+pub enum Error {
+    Loader(loader::Error),
+    Resources(resources::Error),
+    RenderObject(render_object::Error),
+    Gltf(gltf::Error),
+    BadDataUri,
+    BadImage,
+    // reaches past the end of its buffer
+    BadBufferView { view: usize },
+    MissingAttribute { mesh: usize, attribute: &'static str },
+    // doesn't have one value per POSITION
+    AttributeCountMismatch { mesh: usize, attribute: &'static str },
+    // points past the last vertex
+    BadIndex { mesh: usize },
+}
+
+impl From<loader::Error> for Error {
+    fn from(other: loader::Error) -> Self {
+        Error::Loader(other)
+    }
+}
+
+impl From<resources::Error> for Error {
+    fn from(other: resources::Error) -> Self {
+        Error::Resources(other)
+    }
+}
+
+impl From<render_object::Error> for Error {
+    fn from(other: render_object::Error) -> Self {
+        Error::RenderObject(other)
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(other: gltf::Error) -> Self {
+        Error::Gltf(other)
+    }
+}
+
+pub struct ImportedScene {
+    pub objects: Vec<RenderObject>,
+    pub lights: Vec<Light>,
+}
+
+// a material and the g-buffer program able to draw it
+struct ImportedMaterial {
+    program_key: Handle<Program>,
+    material: Material,
+}
+
+pub fn load_gltf(root_path: &FilePath, name: &str, res: &mut Resources) -> Result<ImportedScene, Error> {
+    // the objects hold on to what they draw, whatever no node uses goes with the next remove_released
+    // released on failure too, so a broken file doesn't leave unowned textures and meshes behind
+    let mut created = References::new();
+    let imported = import_gltf(root_path, name, res, &mut created);
+    for key in created.meshes.iter() {
+        res.release_mesh(*key)?;
+    }
+    for key in created.textures.iter() {
+        res.release_texture(*key)?;
+    }
+    imported
+}
+
+// everything it adds to res goes in created
+fn import_gltf(root_path: &FilePath, name: &str, res: &mut Resources, created: &mut References) -> Result<ImportedScene, Error> {
+    let bytes = loader::load_bytes(root_path, name)?;
+    let gltf = gltf::Gltf::from_slice(&bytes)?;
+    let mut blob = gltf.blob.clone();
+    let document = gltf.document;
+
+    let mut buffers: Vec<Vec<u8>> = Vec::with_capacity(document.buffers().len());
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or(Error::Gltf(gltf::Error::MissingBlob))?,
+            gltf::buffer::Source::Uri(uri) => load_uri(root_path, name, uri)?,
+        };
+        buffers.push(data);
+    }
+
+    let mut textures: Vec<Handle<Texture>> = Vec::with_capacity(document.textures().len());
+    for texture in document.textures() {
+        let image_bytes = match texture.source().source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &buffers[view.buffer().index()];
+                buffer.get(view.offset()..view.offset() + view.length())
+                    .ok_or(Error::BadBufferView { view: view.index() })?
+                    .to_vec()
+            },
+            gltf::image::Source::Uri { uri, .. } => load_uri(root_path, name, uri)?,
+        };
+        let image = image::load_from_memory(&image_bytes).map_err(|_| Error::BadImage)?;
+        let mut tex = loader::texture_from_image(loader::expand_to_rgba(image))?;
+        let sampler = sampler_desc(&texture.sampler());
+        if sampler.mip_mode != MipMode::None {
+            tex.generate_mipmaps(MipGeneration::Gpu);
+        }
+        tex.set_sampler(sampler);
+        let key = res.add_texture(tex);
+        created.textures.insert(key);
+        textures.push(key);
+    }
+
+    let mut materials: Vec<ImportedMaterial> = Vec::with_capacity(document.materials().len());
+    let mut white = None;
+    for material in document.materials() {
+        let imported = import_material(&material, &textures, &mut white, res);
+        created.textures.extend(white);
+        materials.push(imported?);
+    }
+    let default_material = ImportedMaterial {
+        program_key: res.get_program_id_by_name("g_program")?,
+        material: Material::make_basic_material(Vector3f::new(1f32, 1f32, 1f32), 0f32, 1f32, 1f32),
+    };
+
+    // one mesh per primitive, primitives of a mesh may use different materials
+    let mut meshes: Vec<Vec<(Handle<Mesh>, Option<usize>)>> = Vec::with_capacity(document.meshes().len());
+    for mesh in document.meshes() {
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b[..]));
+            let positions: Vec<[f32; 3]> = reader.read_positions()
+                .ok_or(Error::MissingAttribute { mesh: mesh.index(), attribute: "POSITION" })?
+                .collect();
+            // zero normals and missing tangents are generated below
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => vec![[0f32, 0f32, 0f32]; positions.len()],
+            };
+            let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|tangents| tangents.collect());
+            let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                Some(tex_coords) => tex_coords.into_f32().collect(),
+                None => vec![[0f32, 0f32]; positions.len()],
+            };
+            let counts = [("NORMAL", normals.len()), ("TEXCOORD_0", tex_coords.len()), ("TANGENT", tangents.as_ref().map_or(positions.len(), Vec::len))];
+            if let Some((attribute, _)) = counts.iter().find(|(_, count)| *count != positions.len()) {
+                return Err(Error::AttributeCountMismatch { mesh: mesh.index(), attribute });
+            }
+            let indices: Vec<i32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as i32).collect(),
+                None => (0..positions.len() as i32).collect(),
+            };
+            // the normal and tangent generators index vertices with these
+            if indices.iter().any(|i| *i < 0 || *i as usize >= positions.len()) {
+                return Err(Error::BadIndex { mesh: mesh.index() });
+            }
+            let (primitive_mode, indices) = primitive_indices(primitive.mode(), indices);
+
+            let mut attributes = vec![AttributeType::Position3D, AttributeType::Normal3D, AttributeType::ST];
+            if tangents.is_some() {
+                attributes.push(AttributeType::Tangent4D);
+            }
+            let vertex_layout = VertexLayout::new(attributes);
+            let mut vertices: Vec<f32> = Vec::with_capacity(positions.len() * vertex_layout.get_num_components());
+            for i in 0..positions.len() {
+                vertices.extend_from_slice(&positions[i]);
+                vertices.extend_from_slice(&normals[i]);
+                vertices.extend_from_slice(&tex_coords[i]);
+                if let Some(tangents) = &tangents {
+                    vertices.extend_from_slice(&tangents[i]);
+                }
+            }
+            let mut data = MeshData::new(vertices, indices, vertex_layout).with_primitive(primitive_mode);
+            ensure_normals(&mut data);
+            ensure_tangents(&mut data);
+            let mesh_key = res.add_mesh(Mesh::new(data));
+            created.meshes.insert(mesh_key);
+            primitives.push((mesh_key, primitive.material().index()));
+        }
+        meshes.push(primitives);
+    }
+
+    let mut imported = ImportedScene {
+        objects: Vec::new(),
+        lights: Vec::new(),
+    };
+    let scene = match document.default_scene() {
+        Some(scene) => Some(scene),
+        None => document.scenes().next(),
+    };
+    if let Some(scene) = scene {
+        for node in scene.nodes() {
+            import_node(&node, &Mat4f::identity(), &meshes, &materials, &default_material, res, &mut imported)?;
+        }
+    }
+    Ok(imported)
+}
+
+// fans and loops have no PrimitiveMode of their own, they're unrolled into lists and strips
+fn primitive_indices(mode: gltf::mesh::Mode, indices: Vec<i32>) -> (PrimitiveMode, Vec<i32>) {
+    use gltf::mesh::Mode;
+    match mode {
+        Mode::Triangles => (PrimitiveMode::Triangles, indices),
+        Mode::TriangleStrip => (PrimitiveMode::TriangleStrip, indices),
+        Mode::TriangleFan => {
+            let mut triangles = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+            for i in 2..indices.len() {
+                triangles.extend_from_slice(&[indices[0], indices[i - 1], indices[i]]);
+            }
+            (PrimitiveMode::Triangles, triangles)
+        },
+        Mode::Lines => (PrimitiveMode::Lines, indices),
+        Mode::LineStrip => (PrimitiveMode::LineStrip, indices),
+        Mode::LineLoop => {
+            let mut strip = indices;
+            if let Some(first) = strip.first().copied() {
+                strip.push(first);
+            }
+            (PrimitiveMode::LineStrip, strip)
+        },
+        Mode::Points => (PrimitiveMode::Points, indices),
+    }
+}
+
+// unspecified filters get trilinear, glTF samplers can't ask for anisotropy so every mipmapped texture gets the default
+fn sampler_desc(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    let wrap = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => Wrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        WrappingMode::Repeat => Wrap::Repeat,
+    };
+    let (min_filter, mip_mode) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (Filter::Nearest, MipMode::None),
+        Some(MinFilter::Linear) => (Filter::Linear, MipMode::None),
+        Some(MinFilter::NearestMipmapNearest) => (Filter::Nearest, MipMode::Nearest),
+        Some(MinFilter::LinearMipmapNearest) => (Filter::Linear, MipMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (Filter::Nearest, MipMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (Filter::Linear, MipMode::Linear),
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => Filter::Nearest,
+        Some(MagFilter::Linear) | None => Filter::Linear,
+    };
+    let mut desc = SamplerDesc::new(min_filter, mip_mode, wrap(sampler.wrap_s()));
+    desc.mag_filter = mag_filter;
+    desc.wrap_t = wrap(sampler.wrap_t());
+    if mip_mode != MipMode::None {
+        desc.anisotropy = DEFAULT_ANISOTROPY;
+    }
+    desc
+}
+
+fn load_uri(root_path: &FilePath, name: &str, uri: &str) -> Result<Vec<u8>, Error> {
+    if uri.starts_with("data:") {
+        let encoded = uri.split(";base64,").nth(1).ok_or(Error::BadDataUri)?;
+        return base64::decode(encoded).map_err(|_| Error::BadDataUri);
+    }
+    Ok(loader::load_bytes(root_path, &loader::sibling_resource_name(name, uri))?)
+}
+
+// 1x1 white texture for the properties g_pbr_program samples but the material doesn't texture, the factor sets the value
+fn white_texture(res: &mut Resources, cache: &mut Option<Handle<Texture>>) -> Handle<Texture> {
+    if let Some(key) = cache {
+        return *key;
+    }
+    let mut tex = Texture::create_with_data(gl::RGBA, gl::RGBA, gl::UNSIGNED_BYTE, (1, 1), vec![255, 255, 255, 255]);
+    tex.set_sampler(SamplerDesc::nearest_clamp());
+    let key = res.add_texture(tex);
+    *cache = Some(key);
+    key
+}
+
+fn import_material(material: &gltf::Material, textures: &[Handle<Texture>], white: &mut Option<Handle<Texture>>, res: &mut Resources) -> Result<ImportedMaterial, Error> {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color = pbr.base_color_factor();
+    let base_color = Vector3f::new(base_color[0], base_color[1], base_color[2]);
+    let base_color_texture = pbr.base_color_texture().map(|info| textures[info.texture().index()]);
+    let metallic_roughness_texture = pbr.metallic_roughness_texture().map(|info| textures[info.texture().index()]);
+    let occlusion_texture = material.occlusion_texture().map(|info| textures[info.texture().index()]);
+    // the factors multiply the textures
+    let factors = TextureFactors {
+        albedo: base_color,
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+    };
+
+    // meshes carry tangents now, but the g-buffer shaders only take vertex normals so far
+    let normal = Normal(MaterialPropertyType::PerVertex);
+
+    if metallic_roughness_texture.is_none() && occlusion_texture.is_none() {
+        let (program_name, albedo) = match base_color_texture {
+            Some(key) => ("g_tex_program", MaterialPropertyType::FromTexture(key)),
+            None => ("g_program", MaterialPropertyType::Constant(base_color)),
+        };
+        return Ok(ImportedMaterial {
+            program_key: res.get_program_id_by_name(program_name)?,
+            material: Material {
+                albedo: Albedo(albedo),
+                normal,
+                metallic: Metallic(MaterialPropertyType::Constant(pbr.metallic_factor())),
+                roughness: Roughness(MaterialPropertyType::Constant(pbr.roughness_factor())),
+                ao: AO(MaterialPropertyType::Constant(1f32)),
+                factors,
+            },
+        });
+    }
+
+    // g_pbr_program samples every property, the ones without a texture sample white times their factor
+    let albedo = base_color_texture.unwrap_or_else(|| white_texture(res, white));
+    let metallic_roughness = metallic_roughness_texture.unwrap_or_else(|| white_texture(res, white));
+    let ao = occlusion_texture.unwrap_or_else(|| white_texture(res, white));
+    Ok(ImportedMaterial {
+        program_key: res.get_program_id_by_name("g_pbr_program")?,
+        material: Material {
+            albedo: Albedo(MaterialPropertyType::FromTexture(albedo)),
+            normal,
+            metallic: Metallic(MaterialPropertyType::FromTexture(metallic_roughness)),
+            roughness: Roughness(MaterialPropertyType::FromTexture(metallic_roughness)),
+            ao: AO(MaterialPropertyType::FromTexture(ao)),
+            factors,
+        },
+    })
+}
+
+fn import_node(
+    node: &gltf::Node,
+    parent: &Mat4f,
+    meshes: &[Vec<(Handle<Mesh>, Option<usize>)>],
+    materials: &[ImportedMaterial],
+    default_material: &ImportedMaterial,
+    res: &Resources,
+    imported: &mut ImportedScene,
+) -> Result<(), Error> {
+    let (t, r, s) = node.transform().decomposed();
+    let local = Mat4f::mult(
+        &Mat4f::translation(Vector3f::new(t[0], t[1], t[2])),
+        &Mat4f::mult(&Mat4f::from_quaternion(r), &Mat4f::scale(Vector3f::new(s[0], s[1], s[2]))),
+    );
+    let world = Mat4f::mult(parent, &local);
+    let transform = Transform::from_mat(&world);
+
+    if let Some(mesh) = node.mesh() {
+        for (mesh_key, material_index) in meshes[mesh.index()].iter() {
+            let material = match material_index {
+                Some(index) => &materials[*index],
+                None => default_material,
+            };
+            imported.objects.push(RenderObject::from_keys(
+                res,
+                transform,
+                material.program_key,
+                *mesh_key,
+                material.material.clone(),
+            )?);
+        }
+    }
+
+    if let Some(light) = node.light() {
+        let color = light.color();
+        let color = Vector3f::new(color[0], color[1], color[2]);
+        imported.lights.push(match light.kind() {
+            Kind::Directional => Light::new_directional_light(transform, color, light.intensity()),
+            // spot cones are not supported by the light pass, treat them as point lights
+            Kind::Point | Kind::Spot { .. } => Light::new_point_light(transform, color, light.intensity()),
+        });
+    }
+
+    for child in node.children() {
+        import_node(&child, &world, meshes, materials, default_material, res, imported)?;
+    }
+    Ok(())
+}
\ No newline at end of file
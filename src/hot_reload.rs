@@ -0,0 +1,139 @@
+/*
+development mode asset reloading
+polls modification times of everything in the manifest, reloaded assets keep their handles
+the manifest itself is only read at startup, new entries need a restart
+*/
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::loader::{FilePath, resource_modified_time};
+use crate::load_assets::{build_program, build_mesh, build_texture, Error};
+use crate::manifest::{Manifest, ProgramDesc, MeshDesc, TextureDesc};
+use crate::resources::{Resources, Handle};
+use crate::mesh::Mesh;
+use crate::shader::Program;
+use crate::texture::Texture;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+enum WatchedKind {
+    Program(Handle<Program>, ProgramDesc),
+    Mesh(Handle<Mesh>, MeshDesc),
+    Texture(Handle<Texture>, TextureDesc),
+}
+
+struct WatchedAsset {
+    kind: WatchedKind,
+    files: Vec<(String, Option<SystemTime>)>,
+}
+
+pub struct AssetWatcher {
+    resource_path: Arc<FilePath>,
+    watched: Vec<WatchedAsset>,
+    last_poll: Instant,
+}
+
+impl AssetWatcher {
+    pub fn new(resource_path: Arc<FilePath>, manifest: &Manifest, res: &Resources) -> Self {
+        let mut watched = Vec::new();
+        for desc in manifest.programs.iter() {
+            if let Ok(key) = res.get_program_id_by_name(&desc.name) {
+                // includes are watched too
+                let files = res.get_program(key).unwrap().source_files.clone();
+                watched.push(WatchedAsset::new(&resource_path, WatchedKind::Program(key, desc.clone()), files));
+            }
+        }
+        for desc in manifest.meshes.iter() {
+            if let Ok(key) = res.get_mesh_id_by_name(&desc.name) {
+                let files = vec![desc.path.clone()];
+                watched.push(WatchedAsset::new(&resource_path, WatchedKind::Mesh(key, desc.clone()), files));
+            }
+        }
+        for desc in manifest.textures.iter() {
+            if let Ok(key) = res.get_texture_id_by_name(&desc.name) {
+                let files = vec![desc.path.clone()];
+                watched.push(WatchedAsset::new(&resource_path, WatchedKind::Texture(key, desc.clone()), files));
+            }
+        }
+        AssetWatcher {
+            resource_path,
+            watched,
+            last_poll: Instant::now(),
+        }
+    }
+    pub fn poll(&mut self, res: &mut Resources) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        for asset in self.watched.iter_mut() {
+            if asset.refresh_times(&self.resource_path) {
+                asset.reload(&self.resource_path, res);
+            }
+        }
+    }
+}
+
+impl WatchedAsset {
+    fn new(resource_path: &FilePath, kind: WatchedKind, files: Vec<String>) -> Self {
+        let mut asset = WatchedAsset {
+            kind,
+            files: Vec::new(),
+        };
+        asset.set_files(resource_path, files);
+        asset
+    }
+    fn set_files(&mut self, resource_path: &FilePath, files: Vec<String>) {
+        self.files = files.into_iter()
+            .map(|name| {
+                let time = resource_modified_time(resource_path, &name);
+                (name, time)
+            })
+            .collect();
+    }
+    // true if any file changed since the last poll
+    fn refresh_times(&mut self, resource_path: &FilePath) -> bool {
+        let mut changed = false;
+        for (name, time) in self.files.iter_mut() {
+            let new_time = resource_modified_time(resource_path, name);
+            if new_time.is_some() && new_time != *time {
+                *time = new_time;
+                changed = true;
+            }
+        }
+        changed
+    }
+    // on failure the old resource stays in place
+    fn reload(&mut self, resource_path: &FilePath, res: &mut Resources) {
+        let resources_error = |error| Error::Resources { asset: self.name().to_string(), error };
+        let mut include_files = None;
+        let result = match &self.kind {
+            WatchedKind::Program(key, desc) => build_program(resource_path, desc)
+                .and_then(|program| {
+                    include_files = Some(program.source_files.clone());
+                    res.replace_program(*key, program).map_err(resources_error)
+                }),
+            WatchedKind::Mesh(key, desc) => build_mesh(resource_path, desc)
+                .and_then(|mesh| res.replace_mesh(*key, mesh).map_err(resources_error)),
+            WatchedKind::Texture(key, desc) => build_texture(resource_path, desc)
+                .and_then(|texture| res.replace_texture(*key, texture).map_err(resources_error)),
+        };
+        // an edit may have added or removed includes
+        if let (Ok(_), Some(files)) = (&result, include_files) {
+            self.set_files(resource_path, files);
+        }
+        match result {
+            Ok(_) => println!("Reloaded {}", self.name()),
+            Err(error) => println!("Reload of {} failed, keeping the old one: {}", self.name(), error),
+        }
+    }
+    fn name(&self) -> &str {
+        match &self.kind {
+            WatchedKind::Program(_, desc) => &desc.name,
+            WatchedKind::Mesh(_, desc) => &desc.name,
+            WatchedKind::Texture(_, desc) => &desc.name,
+        }
+    }
+}
@@ -2,6 +2,8 @@
 use crate::transform::Transform;
 use crate::math::Vector3f;
 
+// lights the light pass shades per frame, also handed to shaders as a define
+pub const MAX_LIGHTS: usize = 4;
 
 pub enum LightType {
     PointLight,
@@ -1,93 +1,246 @@
-use crate::resources::Resources;
-use crate::loader::*;
-use crate::shader::{Shader, Program};
+use crate::resources::{self, Resources};
+use crate::loader::{self, *};
+use crate::shader::{self, Shader, Program};
+use crate::manifest::{self, load_manifest, Manifest, ProgramDesc, MeshDesc, TextureDesc, MaterialDesc, PropertyDesc, SceneDesc};
+use crate::material::{Material, Albedo, Normal, Metallic, Roughness, AO, MaterialPropertyType, TextureFactors};
+use crate::mesh::{Mesh, MeshData, LodData};
+use crate::texture::{Texture, ImageData, MipGeneration};
+use crate::async_loader::AsyncLoader;
+use crate::gltf_loader::{self, load_gltf, ImportedScene};
+use crate::render_object::{self, RenderObject};
+use crate::transform::Transform;
+use crate::math::Vector3f;
+use crate::geo::normals::ensure_normals;
+use crate::geo::tangents::ensure_tangents;
+use crate::geo::attributes;
+use crate::geo::simplify::build_lod_chain;
 use std::path::{Path};
 
-pub fn load_assets(resources: &mut Resources) {
-    let resource_path = from_relative_exe_path(Path::new("assets")).unwrap();
-    {
-        let vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/flat_vert.glsl").unwrap()).unwrap();
-        let frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/flat_frag.glsl").unwrap()).unwrap();
-        let program = Program::from_shaders(&vert_shader, &frag_shader).unwrap();
-        resources.add_program_by_name("flat_program", program).unwrap();
-    }
-    {
-        let obj_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/textured_vert.glsl").unwrap()).unwrap();
-        let obj_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/textured_frag.glsl").unwrap()).unwrap();
-        let obj_program = Program::from_shaders(&obj_vert_shader, &obj_frag_shader).unwrap();
-        resources.add_program_by_name("textured_program", obj_program).unwrap();
-    }
+// every failing asset is reported, not just the first one
+#[derive(Debug)]
+pub enum Error {
+    Manifest(manifest::Error),
+    Loader { asset: String, error: loader::Error },
+    Shader { asset: String, error: shader::Error },
+    Resources { asset: String, error: resources::Error },
+    UnsupportedMeshFormat { asset: String, path: String },
+    UnsupportedSceneFormat { asset: String, path: String },
+    Geometry { asset: String, error: attributes::Error },
+    Gltf { asset: String, error: gltf_loader::Error },
+    RenderObject { asset: String, error: render_object::Error },
+}
 
-    {
-        let obj_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/phong_vert.glsl").unwrap()).unwrap();
-        let obj_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/phong_frag.glsl").unwrap()).unwrap();
-        let obj_program = Program::from_shaders(&obj_vert_shader, &obj_frag_shader).unwrap();
-        resources.add_program_by_name("phong_program", obj_program).unwrap();
+// shader logs are printed as is, everything else through Debug
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Shader { asset, error } => write!(f, "{}: {}", asset, error),
+            Error::Loader { asset, error } => write!(f, "{}: {:?}", asset, error),
+            Error::Resources { asset, error } => write!(f, "{}: {:?}", asset, error),
+            Error::UnsupportedMeshFormat { asset, path } => write!(f, "{}: unsupported mesh format {}", asset, path),
+            Error::UnsupportedSceneFormat { asset, path } => write!(f, "{}: unsupported scene format {}", asset, path),
+            Error::Geometry { asset, error } => write!(f, "{}: {}", asset, error),
+            Error::Gltf { asset, error } => write!(f, "{}: {:?}", asset, error),
+            Error::RenderObject { asset, error } => write!(f, "{}: {}", asset, error),
+            Error::Manifest(error) => write!(f, "manifest: {:?}", error),
+        }
     }
+}
 
+// the pack build.rs puts next to the executable, with mod and override directories on top
+pub fn exe_asset_path() -> Result<FilePath, loader::Error> {
+    let mut resource_path = from_relative_exe_path(Path::new("assets"))?;
+    resource_path.mount_overrides()?;
+    Ok(resource_path)
+}
 
-    {
-        let obj_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/g_vert.glsl").unwrap()).unwrap();
-        let obj_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/g_frag.glsl").unwrap()).unwrap();
-        let obj_program = Program::from_shaders(&obj_vert_shader, &obj_frag_shader).unwrap();
-        resources.add_program_by_name("g_program", obj_program).unwrap();
+// the source tree's assets, so edits show up without a rebuild
+pub fn source_asset_path() -> Result<FilePath, loader::Error> {
+    let mut resource_path = from_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+    resource_path.mount_overrides()?;
+    Ok(resource_path)
+}
+
+// programs and materials are ready on return, meshes and textures start as placeholders and arrive through the loader
+pub fn load_assets(resource_path: &FilePath, resources: &mut Resources, async_loader: &mut AsyncLoader) -> Result<Manifest, Vec<Error>> {
+    let manifest = load_manifest(resource_path, "manifest.json")
+        .map_err(|e| vec![Error::Manifest(e)])?;
+
+    let mut errors = Vec::new();
+    for desc in manifest.programs.iter() {
+        if let Err(e) = load_program(resource_path, desc, resources) {
+            errors.push(e);
+        }
     }
-    {
-        let vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/g_terrain_vert.glsl").unwrap()).unwrap();
-        let frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/g_terrain_frag.glsl").unwrap()).unwrap();
-        let quad_program = Program::from_shaders(&vert_shader, &frag_shader).unwrap();
-        resources.add_program_by_name("g_terrain_program", quad_program).unwrap();
+    for desc in manifest.meshes.iter() {
+        if let Err(e) = async_loader.queue_mesh(desc, resources) {
+            errors.push(e);
+        }
     }
-    {
-        let obj_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/g_tex_vert.glsl").unwrap()).unwrap();
-        let obj_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/g_tex_frag.glsl").unwrap()).unwrap();
-        let obj_program = Program::from_shaders(&obj_vert_shader, &obj_frag_shader).unwrap();
-        resources.add_program_by_name("g_tex_program", obj_program).unwrap();
+    for desc in manifest.textures.iter() {
+        if let Err(e) = async_loader.queue_texture(desc, resources) {
+            errors.push(e);
+        }
     }
-
-    {
-        let obj_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/lightpass_vert.glsl").unwrap()).unwrap();
-        let obj_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/lightpass_frag.glsl").unwrap()).unwrap();
-        let obj_program = Program::from_shaders(&obj_vert_shader, &obj_frag_shader).unwrap();
-        resources.add_program_by_name("lightpass_program", obj_program).unwrap();
+    // materials last, they refer to textures by name
+    for desc in manifest.materials.iter() {
+        if let Err(e) = load_material(desc, resources) {
+            errors.push(e);
+        }
     }
-    
-    {
-        let quad_vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/quad_vert.glsl").unwrap()).unwrap();
-        let quad_frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/quad_frag.glsl").unwrap()).unwrap();
-        let quad_program = Program::from_shaders(&quad_vert_shader, &quad_frag_shader).unwrap();
-        resources.add_program_by_name("quad_program", quad_program).unwrap();
+
+    if errors.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(errors)
     }
-    {
-        let vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/tone_map_vert.glsl").unwrap()).unwrap();
-        let frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/tone_map_frag.glsl").unwrap()).unwrap();
-        let program = Program::from_shaders(&vert_shader, &frag_shader).unwrap();
-        resources.add_program_by_name("tone_map_program", program).unwrap();
+}
+
+// the manifest's scenes, GL thread, call once load_assets is done since they draw with its programs
+pub fn load_scenes(resource_path: &FilePath, manifest: &Manifest, resources: &mut Resources) -> Result<Vec<ImportedScene>, Vec<Error>> {
+    let mut scenes = Vec::new();
+    let mut errors = Vec::new();
+    for desc in manifest.scenes.iter() {
+        match load_scene(resource_path, desc, resources) {
+            Ok(scene) => scenes.push(scene),
+            Err(e) => errors.push(e),
+        }
     }
-    {
-        let vert_shader = Shader::from_vert_source(&load_cstring(&resource_path, "shaders/hud_vert.glsl").unwrap()).unwrap();
-        let frag_shader = Shader::from_frag_source(&load_cstring(&resource_path, "shaders/hud_frag.glsl").unwrap()).unwrap();
-        let program = Program::from_shaders(&vert_shader, &frag_shader).unwrap();
-        resources.add_program_by_name("hud_program", program).unwrap();
+    if errors.is_empty() {
+        Ok(scenes)
+    } else {
+        Err(errors)
     }
-    {
-        let labyrinth_mesh = load_ply(&resource_path, "meshes/labyrinth.ply").unwrap();
-        resources.add_mesh_by_name("lab", labyrinth_mesh).unwrap();
-        let sphere_mesh = load_ply(&resource_path, "meshes/sphere.ply").unwrap();
-        resources.add_mesh_by_name("sphere", sphere_mesh).unwrap();
-        let cube_mesh = load_ply(&resource_path, "meshes/cube.ply").unwrap();
-        resources.add_mesh_by_name("cube", cube_mesh).unwrap();
-        let gun_mesh = load_ply(&resource_path, "meshes/gun.ply").unwrap();
-        resources.add_mesh_by_name("gun", gun_mesh).unwrap();
+}
+
+pub fn load_scene(resource_path: &FilePath, desc: &SceneDesc, resources: &mut Resources) -> Result<ImportedScene, Error> {
+    let extension = desc.path.rsplit('.').next().unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "gltf" | "glb" => load_gltf(resource_path, &desc.path, resources)
+            .map_err(|error| Error::Gltf { asset: desc.name.clone(), error }),
+        "obj" => {
+            let model = load_obj(resource_path, &desc.path, resources)
+                .map_err(|error| Error::Loader { asset: desc.name.clone(), error })?;
+            obj_scene(&model, resources)
+                .map_err(|error| Error::RenderObject { asset: desc.name.clone(), error })
+        },
+        _ => Err(Error::UnsupportedSceneFormat { asset: desc.name.clone(), path: desc.path.clone() }),
     }
+}
 
-    {
-        let mut dumb_texture = load_png(&resource_path, "textures/dumb_texture.png").unwrap();
-        dumb_texture.set_parameters(vec![(gl::TEXTURE_MIN_FILTER, gl::LINEAR), (gl::TEXTURE_MAG_FILTER, gl::LINEAR), (gl::TEXTURE_WRAP_S, gl::REPEAT), (gl::TEXTURE_WRAP_T, gl::REPEAT)]);
-        resources.add_texture_by_name("dumb", dumb_texture).unwrap();
+// every sub mesh at the origin, the objects take over the references the importer held
+fn obj_scene(model: &ObjModel, resources: &mut Resources) -> Result<ImportedScene, render_object::Error> {
+    let mut objects = Vec::with_capacity(model.sub_meshes.len());
+    for sub_mesh in model.sub_meshes.iter() {
+        let material = match sub_mesh.material_key {
+            Some(key) => resources.get_material(key)?.clone(),
+            None => Material::make_basic_material(Vector3f::new(0.8, 0.8, 0.8), 0f32, 0.5f32, 1f32),
+        };
+        // MTL materials only ever sample the albedo
+        let program_name = match material.albedo.0 {
+            MaterialPropertyType::FromTexture(_) => "g_tex_program",
+            _ => "g_program",
+        };
+        let program_key = resources.get_program_id_by_name(program_name)?;
+        objects.push(RenderObject::from_keys(resources, Transform::identity(), program_key, sub_mesh.mesh_key, material)?);
+    }
+    for sub_mesh in model.sub_meshes.iter() {
+        resources.release_mesh(sub_mesh.mesh_key)?;
+        if let Some(key) = sub_mesh.material_key {
+            resources.release_material(key)?;
+        }
     }
+    Ok(ImportedScene {
+        objects,
+        lights: Vec::new(),
+    })
+}
 
+pub fn build_program(resource_path: &FilePath, desc: &ProgramDesc) -> Result<Program, Error> {
+    let shader_error = |error| Error::Shader { asset: desc.name.clone(), error };
 
+    let vert_shader = Shader::from_vert_file(resource_path, &desc.vertex).map_err(shader_error)?;
+    let frag_shader = Shader::from_frag_file(resource_path, &desc.fragment).map_err(shader_error)?;
+    Program::from_shaders(&vert_shader, &frag_shader).map_err(shader_error)
 }
 
+pub fn build_mesh(resource_path: &FilePath, desc: &MeshDesc) -> Result<Mesh, Error> {
+    let data = build_mesh_data(resource_path, desc)?;
+    let lods = build_mesh_lods(&data, desc)?;
+    Ok(Mesh::new(data).with_lods(lods))
+}
 
+// no GL calls, safe off the main thread
+pub fn build_mesh_data(resource_path: &FilePath, desc: &MeshDesc) -> Result<MeshData, Error> {
+    let extension = desc.path.rsplit('.').next().unwrap_or("").to_lowercase();
+    let mut data = match extension.as_str() {
+        "ply" => load_ply_data(resource_path, &desc.path)
+            .map_err(|error| Error::Loader { asset: desc.name.clone(), error })?,
+        _ => return Err(Error::UnsupportedMeshFormat { asset: desc.name.clone(), path: desc.path.clone() }),
+    };
+    ensure_normals(&mut data);
+    ensure_tangents(&mut data);
+    Ok(data)
+}
+
+// the manifest's levels of detail, no GL calls either
+pub fn build_mesh_lods(data: &MeshData, desc: &MeshDesc) -> Result<Vec<LodData>, Error> {
+    let ratios: Vec<f32> = desc.lods.iter().map(|lod| lod.ratio).collect();
+    let lods = build_lod_chain(data, &ratios)
+        .map_err(|error| Error::Geometry { asset: desc.name.clone(), error })?;
+    Ok(lods.into_iter()
+        .zip(desc.lods.iter())
+        .map(|(data, lod)| LodData { data, screen_size: lod.screen_size })
+        .collect())
+}
+
+pub fn build_texture(resource_path: &FilePath, desc: &TextureDesc) -> Result<Texture, Error> {
+    Ok(finish_texture(build_image_data(resource_path, desc)?, desc))
+}
+
+// no GL calls, safe off the main thread
+pub fn build_image_data(resource_path: &FilePath, desc: &TextureDesc) -> Result<ImageData, Error> {
+    load_image_data(resource_path, &desc.path)
+        .map_err(|error| Error::Loader { asset: desc.name.clone(), error })
+}
+
+// GL thread, applies the manifest's mipmap and sampler settings
+pub fn finish_texture(image: ImageData, desc: &TextureDesc) -> Texture {
+    let mut texture = image.into_texture();
+    if desc.mipmaps != MipGeneration::None {
+        texture.generate_mipmaps(desc.mipmaps);
+    }
+    texture.set_sampler(desc.sampler.to_sampler_desc());
+    texture
+}
+
+fn load_program(resource_path: &FilePath, desc: &ProgramDesc, resources: &mut Resources) -> Result<(), Error> {
+    let program = build_program(resource_path, desc)?;
+    resources.add_program_by_name(&desc.name, program)
+        .map_err(|error| Error::Resources { asset: desc.name.clone(), error })?;
+    Ok(())
+}
+
+fn load_material(desc: &MaterialDesc, resources: &mut Resources) -> Result<(), Error> {
+    let resources_error = |error| Error::Resources { asset: desc.name.clone(), error };
+
+    let material = Material {
+        albedo: Albedo(property(&desc.albedo, resources, |c| Vector3f::new(c[0], c[1], c[2])).map_err(resources_error)?),
+        normal: Normal(MaterialPropertyType::PerVertex),
+        metallic: Metallic(property(&desc.metallic, resources, |c| *c).map_err(resources_error)?),
+        roughness: Roughness(property(&desc.roughness, resources, |c| *c).map_err(resources_error)?),
+        ao: AO(property(&desc.ao, resources, |c| *c).map_err(resources_error)?),
+        factors: TextureFactors::default(),
+    };
+    resources.add_material_by_name(&desc.name, material).map_err(resources_error)?;
+    Ok(())
+}
+
+fn property<D, T, F>(desc: &PropertyDesc<D>, resources: &Resources, convert: F) -> Result<MaterialPropertyType<T>, resources::Error>
+    where F: Fn(&D) -> T {
+    Ok(match desc {
+        PropertyDesc::PerVertex => MaterialPropertyType::PerVertex,
+        PropertyDesc::Constant(c) => MaterialPropertyType::Constant(convert(c)),
+        PropertyDesc::Texture(name) => MaterialPropertyType::FromTexture(resources.get_texture_id_by_name(name)?),
+    })
+}
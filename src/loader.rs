@@ -7,14 +7,22 @@ use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::{self, Read};
 use std::ffi;
+use crate::pack::{self, PackReader};
 
 #[derive(Debug)]
 pub enum Error {
     BadFilePath,
     FileContainsNil,
     Io(io::Error),
+    FileNotUtf8,
     BadPlyFile,
     BadPngFile,
+    BadImageFile,
+    UnsupportedImageFormat(String),
+    UnsupportedColorType(String),
+    BadObjFile { line: usize },
+    BadMtlFile { line: usize },
+    Pack(pack::Error),
 }
 
 impl From<io::Error> for Error {
@@ -23,26 +31,131 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<pack::Error> for Error {
+    fn from(other: pack::Error) -> Self {
+        Error::Pack(other)
+    }
+}
+
+enum Source {
+    Directory(PathBuf),
+    Pack(PackReader),
+}
+
+impl Source {
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            Source::Directory(root_path) => resource_name_to_path(root_path, name).is_file(),
+            Source::Pack(pack) => pack.contains(&normalize_resource_name(name)),
+        }
+    }
+}
+
+/*
+layered virtual file system
+mounts are searched from the last one down, so later mounts shadow earlier ones per file
+*/
 pub struct FilePath {
-    root_path: PathBuf,
+    mounts: Vec<Source>,
 }
 
-pub fn from_relative_exe_path(rel_path: &Path) -> Result<FilePath, Error> {
+impl Default for FilePath {
+    fn default() -> Self {
+        FilePath::new()
+    }
+}
+
+// players drop single files in here to override the base assets
+pub const MOD_DIRECTORY: &str = "mods";
+// points at a directory that shadows everything else, handy for testing a single asset
+pub const OVERRIDE_ENV_VAR: &str = "GL_FUN_ASSET_OVERRIDE";
+
+impl FilePath {
+    pub fn new() -> Self {
+        FilePath {
+            mounts: Vec::new(),
+        }
+    }
+    pub fn mount_directory(&mut self, root_path: &Path) {
+        self.mounts.push(Source::Directory(root_path.into()));
+    }
+    pub fn mount_pack(&mut self, pack_path: &Path) -> Result<(), Error> {
+        self.mounts.push(Source::Pack(PackReader::open(pack_path)?));
+        Ok(())
+    }
+    // mod directory next to the exe, then the env override, if they exist
+    pub fn mount_overrides(&mut self) -> Result<(), Error> {
+        let mod_path = exe_directory()?.join(MOD_DIRECTORY);
+        if mod_path.is_dir() {
+            self.mount_directory(&mod_path);
+        }
+        if let Some(override_path) = std::env::var_os(OVERRIDE_ENV_VAR) {
+            let override_path = PathBuf::from(override_path);
+            if override_path.is_dir() {
+                self.mount_directory(&override_path);
+            }
+        }
+        Ok(())
+    }
+    fn find(&self, name: &str) -> Option<&Source> {
+        self.mounts.iter().rev().find(|source| source.contains(name))
+    }
+    // the loose file a resource currently resolves to, None if it comes from a pack or doesn't exist
+    pub fn resolve_loose_path(&self, name: &str) -> Option<PathBuf> {
+        match self.find(name)? {
+            Source::Directory(root_path) => Some(resource_name_to_path(root_path, name)),
+            Source::Pack(_) => None,
+        }
+    }
+}
+
+fn exe_directory() -> Result<PathBuf, Error> {
     let exe_file_name = ::std::env::current_exe()
         .map_err(|_| Error::BadFilePath)?;
 
-    let exe_path = exe_file_name.parent()
-                    .ok_or(Error::BadFilePath)?;
-    
-    Ok(FilePath {
-        root_path: exe_path.join(rel_path),
-    })
+    exe_file_name.parent()
+        .map(|path| path.into())
+        .ok_or(Error::BadFilePath)
+}
+
+// the loose directory and <rel_path>.pack next to the exe, whichever exist, the pack on top
+pub fn from_relative_exe_path(rel_path: &Path) -> Result<FilePath, Error> {
+    let root_path = exe_directory()?.join(rel_path);
+    let pack_path = root_path.with_extension("pack");
+
+    let mut file_path = FilePath::new();
+    if root_path.is_dir() || !pack_path.is_file() {
+        file_path.mount_directory(&root_path);
+    }
+    if pack_path.is_file() {
+        file_path.mount_pack(&pack_path)?;
+    }
+    Ok(file_path)
 }
 
 pub fn from_exe_path() -> Result<FilePath, Error> {
     from_relative_exe_path(Path::new(""))
 }
 
+pub fn from_path(root_path: &Path) -> FilePath {
+    let mut file_path = FilePath::new();
+    file_path.mount_directory(root_path);
+    file_path
+}
+
+pub fn from_pack_path(pack_path: &Path) -> Result<FilePath, Error> {
+    let mut file_path = FilePath::new();
+    file_path.mount_pack(pack_path)?;
+    Ok(file_path)
+}
+
+// last modification time of a resource, None if it can't be read or lives in a pack
+pub fn resource_modified_time(file_path: &FilePath, name: &str) -> Option<std::time::SystemTime> {
+    fs::metadata(file_path.resolve_loose_path(name)?)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 
 fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
     let mut path: PathBuf = root_dir.into();
@@ -53,16 +166,22 @@ fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
     path
 }
 
+// pack entries are stored without "." or "..", resolve them the way the file system would
+fn normalize_resource_name(name: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => { parts.pop(); },
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
 pub fn load_cstring(file_path: &FilePath, name: &str) -> Result<ffi::CString, Error> {
-    let mut file = fs::File::open(
-        resource_name_to_path(&file_path.root_path, name)
-    )?;
-    let mut buffer: Vec<u8> = Vec::with_capacity(
-        file.metadata()?.len() as usize + 1
-    );
-
-    file.read_to_end(&mut buffer)?;
-    
+    let buffer = load_bytes(file_path, name)?;
+
     if buffer.iter().find(|i| **i == 0).is_some() {
         return Err(Error::FileContainsNil);
     }
@@ -70,17 +189,50 @@ pub fn load_cstring(file_path: &FilePath, name: &str) -> Result<ffi::CString, Er
     Ok(unsafe {ffi::CString::from_vec_unchecked(buffer) })
 }
 
-use crate::mesh::{Mesh};
-use crate::vertex::{Vertex, VertexLayout, AttributeType};
+pub fn load_bytes(file_path: &FilePath, name: &str) -> Result<Vec<u8>, Error> {
+    match file_path.find(name) {
+        Some(Source::Directory(root_path)) => {
+            let mut file = fs::File::open(
+                resource_name_to_path(root_path, name)
+            )?;
+            let mut buffer: Vec<u8> = Vec::with_capacity(
+                file.metadata()?.len() as usize
+            );
+
+            file.read_to_end(&mut buffer)?;
+            Ok(buffer)
+        },
+        Some(Source::Pack(pack)) => Ok(pack.read(&normalize_resource_name(name))?),
+        None => Err(Error::Io(io::Error::new(io::ErrorKind::NotFound, name.to_string()))),
+    }
+}
+
+pub fn load_string(file_path: &FilePath, name: &str) -> Result<String, Error> {
+    String::from_utf8(load_bytes(file_path, name)?).map_err(|_| Error::FileNotUtf8)
+}
+
+// resolves a path found inside an asset (mtllib, map_Kd, ...) against the asset's own directory
+pub fn sibling_resource_name(base: &str, rel: &str) -> String {
+    let rel = rel.replace('\\', "/");
+    match base.rfind('/') {
+        Some(i) => format!("{}/{}", &base[..i], rel),
+        None => rel,
+    }
+}
+
+use crate::mesh::{Mesh, MeshData};
+use crate::vertex::{VertexLayout, AttributeType};
 
 pub fn load_ply(root_path: &FilePath, name: &str) -> Result<Mesh, Error> {
-    let mut file = fs::File::open(
-        resource_name_to_path(&root_path.root_path, name )
-    )?;
+    Ok(Mesh::new(load_ply_data(root_path, name)?))
+}
 
-    let mut contents = String::new();
+// no GL calls, safe off the main thread
+pub fn load_ply_data(root_path: &FilePath, name: &str) -> Result<MeshData, Error> {
+    parse_ply(&load_string(root_path, name)?)
+}
 
-    file.read_to_string(&mut contents).unwrap();
+fn parse_ply(contents: &str) -> Result<MeshData, Error> {
     let lines: Vec<&str> = contents.split('\n').collect();
 
     let mut vertex_start_line = 0;
@@ -89,13 +241,14 @@ pub fn load_ply(root_path: &FilePath, name: &str) -> Result<Mesh, Error> {
 
     for (i, line) in lines.iter().enumerate() {
         let words: Vec<&str> = line.split_whitespace().collect();
-        if words[0] == "element" && words[1] == "vertex" {
-            vertex_count = words[2].parse().unwrap();
-        } else if words[0] == "element" && words[1] == "face" {
-            face_count = words[2].parse().unwrap();
-        } else if words[0] == "end_header" {
-            vertex_start_line = i+1;
-            break
+        match words.as_slice() {
+            ["element", "vertex", count, ..] => vertex_count = count.parse().map_err(|_| Error::BadPlyFile)?,
+            ["element", "face", count, ..] => face_count = count.parse().map_err(|_| Error::BadPlyFile)?,
+            ["end_header", ..] => {
+                vertex_start_line = i+1;
+                break
+            },
+            _ => (),
         }
     }
     let vertex_layout = VertexLayout::new(vec![AttributeType::Position3D, AttributeType::Normal3D, AttributeType::ST]);
@@ -103,55 +256,535 @@ pub fn load_ply(root_path: &FilePath, name: &str) -> Result<Mesh, Error> {
     let mut indices: Vec<i32> = Vec::with_capacity(3 * face_count);
     // collect vertices
     for vertex_line in 0..vertex_count {
-        let line = lines[vertex_line + vertex_start_line];
+        let line = lines.get(vertex_line + vertex_start_line).ok_or(Error::BadPlyFile)?;
         let words: Vec<&str> = line.split_whitespace().collect();
-        
-        vertices.push(words[0].parse().unwrap());
-        vertices.push(words[1].parse().unwrap());
-        vertices.push(words[2].parse().unwrap());
-        vertices.push(words[3].parse().unwrap());
-        vertices.push(words[4].parse().unwrap());
-        vertices.push(words[5].parse().unwrap());
-        vertices.push(words[6].parse().unwrap());
-        vertices.push(words[7].parse().unwrap());
+        if words.len() < 8 {
+            return Err(Error::BadPlyFile);
+        }
+        for word in words[0..8].iter() {
+            vertices.push(word.parse().map_err(|_| Error::BadPlyFile)?);
+        }
     }
 
     for index in 0..face_count {
-        let line = lines[index + vertex_start_line + vertex_count];
+        let line = lines.get(index + vertex_start_line + vertex_count).ok_or(Error::BadPlyFile)?;
         let words: Vec<&str> = line.split_whitespace().collect();
-
-        indices.push(words[1].parse().unwrap());
-        indices.push(words[2].parse().unwrap());
-        indices.push(words[3].parse().unwrap());
+        if words.len() < 4 {
+            return Err(Error::BadPlyFile);
+        }
+        for word in words[1..4].iter() {
+            let vertex: i32 = word.parse().map_err(|_| Error::BadPlyFile)?;
+            // the normal and tangent generators index vertices with these
+            if vertex < 0 || vertex as usize >= vertex_count {
+                return Err(Error::BadPlyFile);
+            }
+            indices.push(vertex);
+        }
     }
 
-    Ok(Mesh::new(Vertex::new(vertices, indices, vertex_layout)))
+    Ok(MeshData::new(vertices, indices, vertex_layout))
 }
 
-use image::codecs::png::{PngDecoder};
-use image::ImageDecoder;
-use image::ColorType;
-use crate::texture::Texture;
+use image::{ColorType, DynamicImage, GenericImageView, ImageFormat};
+use crate::texture::{Texture, ImageData, SamplerDesc, MipGeneration, DEFAULT_ANISOTROPY};
 
 pub fn load_png(root_path: &FilePath, name: &str) -> Result<Texture, Error> {
-    let file = fs::File::open(
-        resource_name_to_path(&root_path.root_path, name)
-    )?;
-
-    let decoder = PngDecoder::new(file).map_err(|_| Error::BadPngFile)?;
-
-    let dimensions = decoder.dimensions();
-    let color_type_format = decoder.color_type();
-    
-    let format = match color_type_format {
-        ColorType::Rgb16 => (gl::RGB, gl::UNSIGNED_SHORT),
-        ColorType::Rgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
-        _ => panic!("No matching opengl type for {:?}", color_type_format),
+    let bytes = load_bytes(root_path, name)?;
+    let image = image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+        .map_err(|_| Error::BadPngFile)?;
+    texture_from_image(image)
+}
+
+// png, jpeg, tga or bmp, picked by file extension, keeping the file's own channel layout
+pub fn load_image(root_path: &FilePath, name: &str) -> Result<Texture, Error> {
+    texture_from_image(decode_image(root_path, name)?)
+}
+
+// same as load_image but always RGBA, for shaders that read .a or expect color in .rgb
+pub fn load_image_rgba(root_path: &FilePath, name: &str) -> Result<Texture, Error> {
+    texture_from_image(expand_to_rgba(decode_image(root_path, name)?))
+}
+
+fn decode_image(root_path: &FilePath, name: &str) -> Result<DynamicImage, Error> {
+    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    let format = match extension.as_str() {
+        "png" => ImageFormat::Png,
+        "jpg" | "jpeg" => ImageFormat::Jpeg,
+        "tga" => ImageFormat::Tga,
+        "bmp" => ImageFormat::Bmp,
+        _ => return Err(Error::UnsupportedImageFormat(extension)),
     };
+    let bytes = load_bytes(root_path, name)?;
+    image::load_from_memory_with_format(&bytes, format).map_err(|_| Error::BadImageFile)
+}
+
+pub fn expand_to_rgba(image: DynamicImage) -> DynamicImage {
+    match image.color() {
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    }
+}
+
+// (internal format, format, data type)
+pub fn gl_format_for_color_type(color_type: ColorType) -> Result<(gl::types::GLenum, gl::types::GLenum, gl::types::GLenum), Error> {
+    match color_type {
+        ColorType::L8 => Ok((gl::R8, gl::RED, gl::UNSIGNED_BYTE)),
+        ColorType::La8 => Ok((gl::RG8, gl::RG, gl::UNSIGNED_BYTE)),
+        ColorType::Rgb8 => Ok((gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE)),
+        ColorType::Rgba8 => Ok((gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE)),
+        ColorType::Bgr8 => Ok((gl::RGB8, gl::BGR, gl::UNSIGNED_BYTE)),
+        ColorType::Bgra8 => Ok((gl::RGBA8, gl::BGRA, gl::UNSIGNED_BYTE)),
+        ColorType::L16 => Ok((gl::R16, gl::RED, gl::UNSIGNED_SHORT)),
+        ColorType::La16 => Ok((gl::RG16, gl::RG, gl::UNSIGNED_SHORT)),
+        ColorType::Rgb16 => Ok((gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT)),
+        ColorType::Rgba16 => Ok((gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT)),
+        _ => Err(Error::UnsupportedColorType(format!("{:?}", color_type))),
+    }
+}
+
+pub fn texture_from_image(image: DynamicImage) -> Result<Texture, Error> {
+    Ok(image_data_from_image(image)?.into_texture())
+}
+
+// no GL calls, safe off the main thread
+pub fn load_image_data(root_path: &FilePath, name: &str) -> Result<ImageData, Error> {
+    image_data_from_image(decode_image(root_path, name)?)
+}
 
-    let mut bytes: Vec<u8> = vec![0; (dimensions.0 * dimensions.1) as usize * color_type_format.bytes_per_pixel() as usize];
+pub fn image_data_from_image(image: DynamicImage) -> Result<ImageData, Error> {
+    let color_type = image.color();
+    let (internal_format, format, data_type) = gl_format_for_color_type(color_type)?;
+    let dimensions = image.dimensions();
 
-    decoder.read_image(&mut bytes[..]).map_err(|_| Error::BadPngFile)?;
+    // grayscale reads as gray instead of red, luma-alpha keeps its alpha
+    let swizzle = match color_type {
+        ColorType::L8 | ColorType::L16 => vec![(gl::TEXTURE_SWIZZLE_G, gl::RED), (gl::TEXTURE_SWIZZLE_B, gl::RED)],
+        ColorType::La8 | ColorType::La16 => vec![(gl::TEXTURE_SWIZZLE_G, gl::RED), (gl::TEXTURE_SWIZZLE_B, gl::RED), (gl::TEXTURE_SWIZZLE_A, gl::GREEN)],
+        _ => Vec::new(),
+    };
+    Ok(ImageData {
+        internal_format,
+        format,
+        data_type,
+        dimensions,
+        bytes: image.into_bytes(),
+        swizzle,
+    })
+}
 
-    Ok(Texture::create_with_data(gl::RGBA, format.0, format.1, dimensions, bytes))
-}
\ No newline at end of file
+/*
+Radiance HDR
+equirectangular environment maps, kept as linear float RGB
+*/
+use crate::texture::{equirect_to_cube_faces, f32_to_bytes, Wrap};
+
+pub struct HdrImage {
+    pub dimensions: (u32, u32),
+    pub pixels: Vec<f32>,
+}
+
+pub fn load_hdr(root_path: &FilePath, name: &str) -> Result<HdrImage, Error> {
+    let bytes = load_bytes(root_path, name)?;
+    let decoder = image::codecs::hdr::HdrDecoder::new(io::Cursor::new(bytes)).map_err(|_| Error::BadImageFile)?;
+    let metadata = decoder.metadata();
+    let texels = decoder.read_image_hdr().map_err(|_| Error::BadImageFile)?;
+
+    let mut pixels: Vec<f32> = Vec::with_capacity(texels.len() * 3);
+    for texel in texels.iter() {
+        pixels.extend_from_slice(&texel.0);
+    }
+    Ok(HdrImage {
+        dimensions: (metadata.width, metadata.height),
+        pixels,
+    })
+}
+
+pub fn load_hdr_texture(root_path: &FilePath, name: &str) -> Result<Texture, Error> {
+    let hdr = load_hdr(root_path, name)?;
+    let mut texture = Texture::create_with_data(gl::RGB16F, gl::RGB, gl::FLOAT, hdr.dimensions, f32_to_bytes(&hdr.pixels));
+    // longitude wraps around, latitude stops at the poles
+    let mut sampler = SamplerDesc::linear_repeat();
+    sampler.wrap_t = Wrap::ClampToEdge;
+    texture.set_sampler(sampler);
+    Ok(texture)
+}
+
+pub fn load_hdr_cubemap(root_path: &FilePath, name: &str, face_size: u32) -> Result<Texture, Error> {
+    let hdr = load_hdr(root_path, name)?;
+    let faces = equirect_to_cube_faces(hdr.dimensions, &hdr.pixels, face_size)
+        .iter()
+        .map(|face| f32_to_bytes(face))
+        .collect();
+    let mut texture = Texture::create_cubemap_with_data(gl::RGB16F, gl::RGB, gl::FLOAT, face_size, faces);
+    texture.set_sampler(SamplerDesc::linear_clamp());
+    Ok(texture)
+}
+
+/*
+Wavefront OBJ + MTL
+every (group, material) pair becomes its own mesh so sub-meshes can be drawn with different materials
+*/
+use std::collections::HashMap;
+use crate::resources::{Resources, Handle};
+use crate::material::{Material, Albedo, Normal, Metallic, Roughness, AO, MaterialPropertyType, TextureFactors};
+use crate::math::{Vector2f, Vector3f};
+use crate::geo::triangulate::triangulate;
+use crate::geo::normals::ensure_normals;
+use crate::geo::tangents::ensure_tangents;
+
+pub struct ObjSubMesh {
+    pub name: String,
+    pub mesh_key: Handle<Mesh>,
+    pub material_name: Option<String>,
+    pub material_key: Option<Handle<Material>>,
+}
+
+pub struct ObjModel {
+    pub sub_meshes: Vec<ObjSubMesh>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum ObjNormalSource {
+    Index(usize),
+    // no vn given, flat normal of the face it belongs to
+    Face(usize),
+}
+
+struct ObjGroup {
+    name: String,
+    material: Option<String>,
+    vertices: Vec<f32>,
+    indices: Vec<i32>,
+    index_map: HashMap<(usize, Option<usize>, ObjNormalSource), i32>,
+}
+
+impl ObjGroup {
+    fn new(name: String, material: Option<String>) -> Self {
+        ObjGroup {
+            name,
+            material,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            index_map: HashMap::new(),
+        }
+    }
+}
+
+fn obj_index(word: &str, count: usize, line: usize) -> Result<usize, Error> {
+    let i: i64 = word.parse().map_err(|_| Error::BadObjFile { line })?;
+    let index = if i > 0 {
+        i - 1
+    } else {
+        // negative indices count back from the most recent element
+        count as i64 + i
+    };
+    if i == 0 || index < 0 || index >= count as i64 {
+        return Err(Error::BadObjFile { line });
+    }
+    Ok(index as usize)
+}
+
+// Newell's normal, right for concave and slightly bent n-gons too, not normalized
+fn polygon_normal(points: &[Vector3f]) -> Vector3f {
+    let mut normal = Vector3f::zero();
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        normal.add_to(&Vector3f::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        ));
+    }
+    normal
+}
+
+/*
+the face flattened onto the plane its normal points at most, in an order that keeps it counter clockwise,
+so the ear clipper's counter clockwise triangles wind like the face
+*/
+fn project_face(points: &[Vector3f], normal: &Vector3f) -> Vec<Vector2f> {
+    let (x, y, z) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+    points.iter()
+        .map(|p| if z >= x && z >= y {
+            if normal.z > 0f32 { Vector2f::new(p.x, p.y) } else { Vector2f::new(p.y, p.x) }
+        } else if x >= y {
+            if normal.x > 0f32 { Vector2f::new(p.y, p.z) } else { Vector2f::new(p.z, p.y) }
+        } else if normal.y > 0f32 {
+            Vector2f::new(p.z, p.x)
+        } else {
+            Vector2f::new(p.x, p.z)
+        })
+        .collect()
+}
+
+fn parse_floats(words: &[&str], n: usize) -> Option<Vec<f32>> {
+    if words.len() < n {
+        return None;
+    }
+    words[..n].iter().map(|w| w.parse::<f32>().ok()).collect()
+}
+
+pub fn load_obj(root_path: &FilePath, name: &str, res: &mut Resources) -> Result<ObjModel, Error> {
+    let contents = load_string(root_path, name)?;
+
+    let mut positions: Vec<Vector3f> = Vec::new();
+    let mut normals: Vec<Vector3f> = Vec::new();
+    let mut texcoords: Vec<(f32, f32)> = Vec::new();
+
+    let mut groups: Vec<ObjGroup> = Vec::new();
+    let mut group_lookup: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut current_name = String::from("default");
+    let mut current_material: Option<String> = None;
+    let mut material_libs: Vec<String> = Vec::new();
+    let mut face_count = 0;
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+        let bad_line = || Error::BadObjFile { line: line_number };
+        match words[0] {
+            "v" => {
+                let v = parse_floats(&words[1..], 3).ok_or_else(bad_line)?;
+                positions.push(Vector3f::new(v[0], v[1], v[2]));
+            },
+            "vn" => {
+                let v = parse_floats(&words[1..], 3).ok_or_else(bad_line)?;
+                normals.push(Vector3f::new(v[0], v[1], v[2]));
+            },
+            "vt" => {
+                let v = parse_floats(&words[1..], 2).ok_or_else(bad_line)?;
+                texcoords.push((v[0], v[1]));
+            },
+            "o" | "g" => {
+                current_name = if words.len() > 1 { words[1..].join(" ") } else { String::from("default") };
+            },
+            "usemtl" => {
+                current_material = words.get(1).map(|w| w.to_string());
+            },
+            "mtllib" => {
+                for lib in words[1..].iter() {
+                    material_libs.push(sibling_resource_name(name, lib));
+                }
+            },
+            "f" => {
+                if words.len() < 4 {
+                    return Err(bad_line());
+                }
+                let mut corners: Vec<(usize, Option<usize>, Option<usize>)> = Vec::with_capacity(words.len() - 1);
+                for word in words[1..].iter() {
+                    let parts: Vec<&str> = word.split('/').collect();
+                    let v = obj_index(parts[0], positions.len(), line_number)?;
+                    let vt = match parts.get(1) {
+                        Some(p) if !p.is_empty() => Some(obj_index(p, texcoords.len(), line_number)?),
+                        _ => None,
+                    };
+                    let vn = match parts.get(2) {
+                        Some(p) if !p.is_empty() => Some(obj_index(p, normals.len(), line_number)?),
+                        _ => None,
+                    };
+                    corners.push((v, vt, vn));
+                }
+
+                let face_points: Vec<Vector3f> = corners.iter().map(|corner| positions[corner.0]).collect();
+                let face_normal = {
+                    let mut n = polygon_normal(&face_points);
+                    if n.is_zero() {
+                        Vector3f::new(0f32, 1f32, 0f32)
+                    } else {
+                        n.normalize();
+                        n
+                    }
+                };
+
+                let key = (current_name.clone(), current_material.clone());
+                let group_index = match group_lookup.get(&key) {
+                    Some(index) => *index,
+                    None => {
+                        groups.push(ObjGroup::new(key.0.clone(), key.1.clone()));
+                        group_lookup.insert(key, groups.len() - 1);
+                        groups.len() - 1
+                    }
+                };
+                let group = &mut groups[group_index];
+
+                let mut corner_indices: Vec<i32> = Vec::with_capacity(corners.len());
+                for (v, vt, vn) in corners.iter() {
+                    let normal_source = match vn {
+                        Some(n) => ObjNormalSource::Index(*n),
+                        None => ObjNormalSource::Face(face_count),
+                    };
+                    let vertex_key = (*v, *vt, normal_source);
+                    let index = match group.index_map.get(&vertex_key) {
+                        Some(index) => *index,
+                        None => {
+                            let position = positions[*v];
+                            let normal = match vn {
+                                Some(n) => normals[*n],
+                                None => face_normal,
+                            };
+                            let st = match vt {
+                                Some(t) => texcoords[*t],
+                                None => (0f32, 0f32),
+                            };
+                            let index = (group.vertices.len() / 8) as i32;
+                            group.vertices.extend_from_slice(&[
+                                position.x, position.y, position.z,
+                                normal.x, normal.y, normal.z,
+                                st.0, st.1,
+                            ]);
+                            group.index_map.insert(vertex_key, index);
+                            index
+                        }
+                    };
+                    corner_indices.push(index);
+                }
+
+                // exporters write concave n-gons too, so they're ear clipped instead of fanned
+                if corner_indices.len() == 3 {
+                    group.indices.extend_from_slice(&corner_indices);
+                } else {
+                    for [a, b, c] in triangulate(&project_face(&face_points, &face_normal)).iter().copied() {
+                        group.indices.extend_from_slice(&[corner_indices[a], corner_indices[b], corner_indices[c]]);
+                    }
+                }
+                face_count += 1;
+            },
+            _ => (),
+        }
+    }
+
+    let mut material_keys: HashMap<String, Handle<Material>> = HashMap::new();
+    let mut texture_keys: HashMap<String, Handle<Texture>> = HashMap::new();
+    for lib in material_libs.iter() {
+        for (material_name, material) in load_mtl(root_path, lib, res, &mut texture_keys)? {
+            material_keys.insert(material_name, res.add_material(material));
+        }
+    }
+    // the materials hold their textures, so releasing ours lets them go with the materials
+    for key in texture_keys.values() {
+        res.release_texture(*key).unwrap();
+    }
+
+    let mut sub_meshes = Vec::with_capacity(groups.len());
+    for group in groups.into_iter() {
+        let vertex_layout = VertexLayout::new(vec![AttributeType::Position3D, AttributeType::Normal3D, AttributeType::ST]);
+        let mut data = MeshData::new(group.vertices, group.indices, vertex_layout);
+        ensure_normals(&mut data);
+        ensure_tangents(&mut data);
+        let mesh = Mesh::new(data);
+        let material_key = group.material.as_ref().and_then(|m| material_keys.get(m).copied());
+        sub_meshes.push(ObjSubMesh {
+            name: group.name,
+            mesh_key: res.add_mesh(mesh),
+            material_name: group.material,
+            material_key,
+        });
+    }
+
+    Ok(ObjModel {
+        sub_meshes,
+    })
+}
+
+struct MtlEntry {
+    name: String,
+    diffuse: Vector3f,
+    shininess: Option<f32>,
+    roughness: Option<f32>,
+    metallic: Option<f32>,
+    diffuse_map: Option<String>,
+    normal_map: Option<String>,
+}
+
+impl MtlEntry {
+    fn new(name: String) -> Self {
+        MtlEntry {
+            name,
+            diffuse: Vector3f::new(0.8, 0.8, 0.8),
+            shininess: None,
+            roughness: None,
+            metallic: None,
+            diffuse_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+fn load_mtl(root_path: &FilePath, name: &str, res: &mut Resources, texture_keys: &mut HashMap<String, Handle<Texture>>) -> Result<Vec<(String, Material)>, Error> {
+    let contents = load_string(root_path, name)?;
+    let mut entries: Vec<MtlEntry> = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+        let bad_line = || Error::BadMtlFile { line: line_number };
+        if words[0] == "newmtl" {
+            let material_name = words.get(1).ok_or_else(bad_line)?;
+            entries.push(MtlEntry::new(material_name.to_string()));
+            continue;
+        }
+        let entry = match entries.last_mut() {
+            Some(entry) => entry,
+            None => return Err(bad_line()),
+        };
+        match words[0] {
+            "Kd" => {
+                let v = parse_floats(&words[1..], 3).ok_or_else(bad_line)?;
+                entry.diffuse = Vector3f::new(v[0], v[1], v[2]);
+            },
+            "Ns" => entry.shininess = Some(parse_floats(&words[1..], 1).ok_or_else(bad_line)?[0]),
+            "Pr" => entry.roughness = Some(parse_floats(&words[1..], 1).ok_or_else(bad_line)?[0]),
+            "Pm" => entry.metallic = Some(parse_floats(&words[1..], 1).ok_or_else(bad_line)?[0]),
+            // texture options (-s, -o, -bm ...) come before the file name
+            "map_Kd" => entry.diffuse_map = Some(words.last().ok_or_else(bad_line)?.to_string()),
+            "map_Bump" | "map_bump" | "bump" | "norm" => entry.normal_map = Some(words.last().ok_or_else(bad_line)?.to_string()),
+            _ => (),
+        }
+    }
+
+    let mut materials = Vec::with_capacity(entries.len());
+    for entry in entries.into_iter() {
+        let albedo = match &entry.diffuse_map {
+            Some(map) => MaterialPropertyType::FromTexture(load_mtl_texture(root_path, &sibling_resource_name(name, map), res, texture_keys)?),
+            None => MaterialPropertyType::Constant(entry.diffuse),
+        };
+        let normal = match &entry.normal_map {
+            Some(map) => MaterialPropertyType::FromTexture(load_mtl_texture(root_path, &sibling_resource_name(name, map), res, texture_keys)?),
+            None => MaterialPropertyType::PerVertex,
+        };
+        // Blinn-Phong exponent to GGX roughness, Pr from the PBR extension wins if present
+        let roughness = match (entry.roughness, entry.shininess) {
+            (Some(r), _) => r,
+            (None, Some(ns)) => (2f32 / (ns.max(0f32) + 2f32)).sqrt(),
+            (None, None) => 0.5,
+        };
+        materials.push((entry.name, Material {
+            albedo: Albedo(albedo),
+            normal: Normal(normal),
+            metallic: Metallic(MaterialPropertyType::Constant(entry.metallic.unwrap_or(0f32))),
+            roughness: Roughness(MaterialPropertyType::Constant(roughness.clamp(0f32, 1f32))),
+            ao: AO(MaterialPropertyType::Constant(1f32)),
+            factors: TextureFactors::default(),
+        }));
+    }
+    Ok(materials)
+}
+
+fn load_mtl_texture(root_path: &FilePath, name: &str, res: &mut Resources, texture_keys: &mut HashMap<String, Handle<Texture>>) -> Result<Handle<Texture>, Error> {
+    if let Some(key) = texture_keys.get(name) {
+        return Ok(*key);
+    }
+    let mut texture = load_image(root_path, name)?;
+    texture.generate_mipmaps(MipGeneration::Gpu);
+    texture.set_sampler(SamplerDesc::trilinear_repeat(DEFAULT_ANISOTROPY));
+    let key = res.add_texture(texture);
+    texture_keys.insert(name.to_string(), key);
+    Ok(key)
+}
@@ -6,13 +6,21 @@ pub mod camera;
 pub mod transform;
 pub mod mesh;
 pub mod loader;
+pub mod writer;
+pub mod pack;
+pub mod gltf_loader;
 pub mod texture;
 pub mod render_object;
 pub mod vertex;
+pub mod stream_buffer;
 pub mod gl_util;
 pub mod geo;
 pub mod resources;
 pub mod load_assets;
+pub mod manifest;
+pub mod hot_reload;
+pub mod async_loader;
+pub mod memory;
 pub mod light;
 pub mod framebuffer;
 pub mod material;
@@ -1,7 +1,15 @@
 use crate::resources::Resources;
-use crate::load_assets::load_assets;
+use crate::load_assets::{load_assets, load_scenes, exe_asset_path, source_asset_path};
+use crate::gltf_loader::ImportedScene;
+use crate::hot_reload::AssetWatcher;
+use crate::async_loader::{AsyncLoader, LoadProgress};
 use crate::input::InputState;
 use crate::timer::Timer;
+use std::sync::Arc;
+use std::time::Duration;
+
+// time per frame spent on uploading finished assets to the GPU
+const ASSET_UPLOAD_BUDGET: Duration = Duration::from_millis(4);
 
 /*
 A singleton to rule all singletons, inject into every function
@@ -10,20 +18,79 @@ pub struct Manager {
     pub res: Resources,
     pub input: InputState,
     pub timer: Timer,
+    pub asset_loader: AsyncLoader,
+    // only in dev mode
+    pub asset_watcher: Option<AssetWatcher>,
+    // the manifest's scenes until the game takes them
+    imported_scenes: Vec<ImportedScene>,
 }
 
 impl Manager {
     pub fn new() -> Self {
+        // --dev loads straight from the source tree and reloads assets as they change
+        let dev_mode = std::env::args().any(|arg| arg == "--dev");
+        let resource_path = if dev_mode {
+            source_asset_path()
+        } else {
+            exe_asset_path()
+        }.unwrap();
+        let resource_path = Arc::new(resource_path);
+
         let mut res = Resources::new();
-        load_assets(&mut res);
+        let mut asset_loader = AsyncLoader::new(resource_path.clone());
+        let manifest = match load_assets(&resource_path, &mut res, &mut asset_loader) {
+            Ok(manifest) => manifest,
+            Err(errors) => {
+                for error in errors.iter() {
+                    println!("Asset error: {}", error);
+                }
+                panic!("failed to load {} assets", errors.len());
+            }
+        };
+        let imported_scenes = match load_scenes(&resource_path, &manifest, &mut res) {
+            Ok(scenes) => scenes,
+            Err(errors) => {
+                for error in errors.iter() {
+                    println!("Asset error: {}", error);
+                }
+                panic!("failed to load {} scenes", errors.len());
+            }
+        };
+        let asset_watcher = if dev_mode {
+            Some(AssetWatcher::new(resource_path, &manifest, &res))
+        } else {
+            None
+        };
         let mut timer = Timer::new();
         let mut input = InputState::new();
         Manager {
             res,
             timer,
             input,
+            asset_loader,
+            asset_watcher,
+            imported_scenes,
+        }
+    }
+    pub fn take_imported_scenes(&mut self) -> Vec<ImportedScene> {
+        std::mem::take(&mut self.imported_scenes)
+    }
+    pub fn poll_asset_changes(&mut self) {
+        if let Some(watcher) = self.asset_watcher.as_mut() {
+            watcher.poll(&mut self.res);
         }
     }
+    pub fn upload_pending_assets(&mut self) {
+        self.asset_loader.upload(&mut self.res, ASSET_UPLOAD_BUDGET);
+    }
+    // released resources go once nothing holds them anymore, call once a frame
+    pub fn unload_released_assets(&mut self) {
+        self.res.remove_released();
+    }
+    // for a loading screen
+    pub fn load_progress(&self) -> LoadProgress {
+        self.asset_loader.progress()
+    }
     pub fn get_res(&self) -> &Resources {
         &self.res
     }
@@ -0,0 +1,147 @@
+/*
+asset manifest
+everything load_assets registers is listed in assets/manifest.json
+*/
+
+use serde::Deserialize;
+use crate::loader::{self, FilePath};
+use crate::texture::{SamplerDesc, Filter, MipMode, Wrap, MipGeneration};
+
+#[derive(Debug)]
+pub enum Error {
+    Loader(loader::Error),
+    BadManifest(String),
+}
+
+impl From<loader::Error> for Error {
+    fn from(other: loader::Error) -> Self {
+        Error::Loader(other)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub programs: Vec<ProgramDesc>,
+    #[serde(default)]
+    pub meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    pub textures: Vec<TextureDesc>,
+    #[serde(default)]
+    pub materials: Vec<MaterialDesc>,
+    #[serde(default)]
+    pub scenes: Vec<SceneDesc>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ProgramDesc {
+    pub name: String,
+    pub vertex: String,
+    pub fragment: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MeshDesc {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub lods: Vec<LodDesc>,
+}
+
+// a simplified level built when the mesh loads
+#[derive(Deserialize, Clone)]
+pub struct LodDesc {
+    // of the full mesh's triangles
+    pub ratio: f32,
+    // drawn while the mesh covers less than this fraction of the screen's height
+    pub screen_size: f32,
+}
+
+// a model that brings its own materials, .obj, .gltf or .glb, added to the scene where it was authored
+#[derive(Deserialize, Clone)]
+pub struct SceneDesc {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TextureDesc {
+    pub name: String,
+    pub path: String,
+    #[serde(default = "default_mipmaps")]
+    pub mipmaps: MipGeneration,
+    #[serde(default)]
+    pub sampler: SamplerSettings,
+}
+
+fn default_mipmaps() -> MipGeneration {
+    MipGeneration::None
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SamplerSettings {
+    pub filter: Filter,
+    pub mip: MipMode,
+    pub wrap: Wrap,
+    pub anisotropy: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        SamplerSettings {
+            filter: Filter::Linear,
+            mip: MipMode::None,
+            wrap: Wrap::Repeat,
+            anisotropy: 1f32,
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn to_sampler_desc(&self) -> SamplerDesc {
+        let mut sampler = SamplerDesc::new(self.filter, self.mip, self.wrap);
+        sampler.anisotropy = self.anisotropy;
+        sampler
+    }
+}
+
+// { "constant": 0.5 }, { "texture": "dumb" } or "per_vertex"
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyDesc<T> {
+    PerVertex,
+    Constant(T),
+    Texture(String),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MaterialDesc {
+    pub name: String,
+    #[serde(default = "default_albedo")]
+    pub albedo: PropertyDesc<[f32; 3]>,
+    #[serde(default = "default_metallic")]
+    pub metallic: PropertyDesc<f32>,
+    #[serde(default = "default_roughness")]
+    pub roughness: PropertyDesc<f32>,
+    #[serde(default = "default_ao")]
+    pub ao: PropertyDesc<f32>,
+}
+
+fn default_albedo() -> PropertyDesc<[f32; 3]> {
+    PropertyDesc::Constant([1f32, 1f32, 1f32])
+}
+fn default_metallic() -> PropertyDesc<f32> {
+    PropertyDesc::Constant(0f32)
+}
+fn default_roughness() -> PropertyDesc<f32> {
+    PropertyDesc::Constant(0.5f32)
+}
+fn default_ao() -> PropertyDesc<f32> {
+    PropertyDesc::Constant(1f32)
+}
+
+pub fn load_manifest(root_path: &FilePath, name: &str) -> Result<Manifest, Error> {
+    let source = loader::load_string(root_path, name)?;
+    serde_json::from_str(&source).map_err(|e| Error::BadManifest(e.to_string()))
+}
@@ -4,31 +4,67 @@ dream: construct and compile needed shaders based on material property types at
 */
 
 use crate::shader::Program;
-use crate::resources::{ResourceKey, Resources};
+use crate::resources::{Handle, Resources};
+use crate::texture::Texture;
 use crate::math::Vector3f;
 
+#[derive(Clone)]
 pub enum MaterialPropertyType<T> {
     PerVertex,
     Constant(T),
-    FromTexture(ResourceKey),
+    FromTexture(Handle<Texture>),
 }
 
+impl<T> MaterialPropertyType<T> {
+    pub fn texture_key(&self) -> Option<Handle<Texture>> {
+        match self {
+            MaterialPropertyType::FromTexture(key) => Some(*key),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Albedo( pub MaterialPropertyType<Vector3f> );
 
+#[derive(Clone)]
 pub struct Normal( pub MaterialPropertyType<Vector3f> );
 
+#[derive(Clone)]
 pub struct Metallic( pub MaterialPropertyType<f32> );
 
+#[derive(Clone)]
 pub struct Roughness( pub MaterialPropertyType<f32> );
 
+#[derive(Clone)]
 pub struct AO( pub MaterialPropertyType<f32> );
 
+// multiply the textured properties, like glTF's factors, all ones leave the textures as they are
+#[derive(Clone, Copy)]
+pub struct TextureFactors {
+    pub albedo: Vector3f,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl Default for TextureFactors {
+    fn default() -> Self {
+        TextureFactors {
+            albedo: Vector3f::new(1f32, 1f32, 1f32),
+            metallic: 1f32,
+            roughness: 1f32,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Material {
     pub albedo: Albedo,
     pub normal: Normal,
     pub metallic: Metallic,
     pub roughness: Roughness,
     pub ao: AO,
+    pub factors: TextureFactors,
 }
 
 impl Material {
@@ -39,30 +75,49 @@ impl Material {
             metallic: Metallic(MaterialPropertyType::Constant(metallic)),
             roughness: Roughness(MaterialPropertyType::Constant(roughness)),
             ao: AO(MaterialPropertyType::Constant(ao)),
+            factors: TextureFactors::default(),
         }
     }
     pub fn load_shader_data(&self, res: &Resources, program: &Program) {
+        // texture properties get consecutive units, in the order the g-buffer shaders declare them
+        let texture_slots = [
+            (self.albedo.0.texture_key(), "albedo"),
+            (self.metallic.0.texture_key(), "metallic"),
+            (self.roughness.0.texture_key(), "roughness"),
+            (self.ao.0.texture_key(), "ao"),
+        ];
+        // make sure everything is resident first, an upload goes through unit 0 and would unbind what is already there
+        for (key, _) in texture_slots.iter() {
+            if let Some(key) = key {
+                res.get_texture(*key).unwrap().load_memory();
+            }
+        }
+        for (unit, (key, uniform)) in texture_slots.iter().enumerate() {
+            if let Some(key) = key {
+                res.get_texture(*key).unwrap().bind_to_unit(unit as u32);
+                program.set1i(uniform, unit as i32);
+            }
+        }
+
+        let factors = &self.factors;
         match self.albedo.0 {
             MaterialPropertyType::Constant(v) => program.set3f("albedo", v.x, v.y, v.z).unwrap(),
-            
-            MaterialPropertyType::FromTexture(key) => {
-                let tex = res.get_texture(key).unwrap();
-                tex.load_memory();
-                tex.bind();
-                program.set1i("albedo", 0);
-            },
+            MaterialPropertyType::FromTexture(_) => program.set3f("albedo_factor", factors.albedo.x, factors.albedo.y, factors.albedo.z).unwrap(),
             _ => panic!("Bad material")
         }
         match self.metallic.0 {
             MaterialPropertyType::Constant(f) => program.set1f("metallic", f).unwrap(),
+            MaterialPropertyType::FromTexture(_) => program.set1f("metallic_factor", factors.metallic).unwrap(),
             _ => panic!("Bad material")
         }
         match self.roughness.0 {
             MaterialPropertyType::Constant(f) => program.set1f("roughness", f).unwrap(),
+            MaterialPropertyType::FromTexture(_) => program.set1f("roughness_factor", factors.roughness).unwrap(),
             _ => panic!("Bad material")
         }
         match self.ao.0 {
             MaterialPropertyType::Constant(f) => program.set1f("ao", f).unwrap(),
+            MaterialPropertyType::FromTexture(_) => (),
             _ => panic!("Bad material")
         }
     }
@@ -135,6 +135,7 @@ impl Vector3f {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
 pub struct Vector4f {
     pub x: f32,
     pub y: f32,
@@ -282,6 +283,21 @@ impl Mat4f {
         trans.set(2, 3, t.z);
         trans
     }
+    // unit quaternion (x, y, z, w) to rotation matrix
+    pub fn from_quaternion(q: [f32; 4]) -> Mat4f {
+        let (x, y, z, w) = (q[0], q[1], q[2], q[3]);
+        let mut rot = Mat4f::identity();
+        rot.set(0, 0, 1f32 - 2f32 * (y * y + z * z));
+        rot.set(0, 1, 2f32 * (x * y - z * w));
+        rot.set(0, 2, 2f32 * (x * z + y * w));
+        rot.set(1, 0, 2f32 * (x * y + z * w));
+        rot.set(1, 1, 1f32 - 2f32 * (x * x + z * z));
+        rot.set(1, 2, 2f32 * (y * z - x * w));
+        rot.set(2, 0, 2f32 * (x * z - y * w));
+        rot.set(2, 1, 2f32 * (y * z + x * w));
+        rot.set(2, 2, 1f32 - 2f32 * (x * x + y * y));
+        rot
+    }
     pub fn scale(s: Vector3f) -> Mat4f {
         let mut scale = Mat4f::identity();
         scale.set(0, 0, s.x);
@@ -0,0 +1,218 @@
+/*
+memory accounting for everything Resources and the renderer hold
+cpu sizes are the bytes kept around for uploads, gpu sizes are estimates from buffer lengths, dimensions and formats
+"unreferenced" means nothing in the scene or another resource points at it, named assets from the manifest
+show up there too when the scene doesn't use them, anonymous ones are the likely leaks
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+use crate::shader::Program;
+use crate::material::Material;
+use crate::resources::Handle;
+
+// entries listed in the printed report
+pub const REPORT_TOP_N: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Mesh,
+    Texture,
+    Program,
+    Material,
+    Attachment,
+}
+
+const ALL_KINDS: [ResourceKind; 5] = [
+    ResourceKind::Mesh,
+    ResourceKind::Texture,
+    ResourceKind::Program,
+    ResourceKind::Material,
+    ResourceKind::Attachment,
+];
+
+impl ResourceKind {
+    fn label(&self) -> &'static str {
+        match self {
+            ResourceKind::Mesh => "mesh",
+            ResourceKind::Texture => "texture",
+            ResourceKind::Program => "program",
+            ResourceKind::Material => "material",
+            ResourceKind::Attachment => "attachment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryEntry {
+    pub kind: ResourceKind,
+    pub name: Option<String>,
+    // slot index for resources, None for renderer owned objects
+    pub index: Option<u32>,
+    pub cpu_bytes: usize,
+    pub gpu_bytes: usize,
+    pub resident: bool,
+    pub ref_count: usize,
+    pub referenced: bool,
+}
+
+impl MemoryEntry {
+    pub fn total_bytes(&self) -> usize {
+        self.cpu_bytes + self.gpu_bytes
+    }
+    fn label(&self) -> String {
+        match (&self.name, self.index) {
+            (Some(name), _) => name.clone(),
+            (None, Some(index)) => format!("#{}", index),
+            (None, None) => "?".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct KindTotal {
+    pub kind: ResourceKind,
+    pub count: usize,
+    pub cpu_bytes: usize,
+    pub gpu_bytes: usize,
+}
+
+// handles in use outside of Resources, filled in by whoever owns objects
+#[derive(Default, Clone)]
+pub struct References {
+    pub meshes: HashSet<Handle<Mesh>>,
+    pub textures: HashSet<Handle<Texture>>,
+    pub programs: HashSet<Handle<Program>>,
+    pub materials: HashSet<Handle<Material>>,
+}
+
+impl References {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_material(&mut self, material: &Material) {
+        let keys = [
+            material.albedo.0.texture_key(),
+            material.normal.0.texture_key(),
+            material.metallic.0.texture_key(),
+            material.roughness.0.texture_key(),
+            material.ao.0.texture_key(),
+        ];
+        self.textures.extend(keys.iter().filter_map(|key| *key));
+    }
+}
+
+pub struct MemoryReport {
+    entries: Vec<MemoryEntry>,
+}
+
+impl MemoryReport {
+    pub fn new(mut entries: Vec<MemoryEntry>) -> Self {
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.total_bytes()));
+        MemoryReport {
+            entries,
+        }
+    }
+    pub fn extend(&mut self, entries: Vec<MemoryEntry>) {
+        let mut all = std::mem::take(&mut self.entries);
+        all.extend(entries);
+        *self = MemoryReport::new(all);
+    }
+    // largest first
+    pub fn entries(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+    pub fn top(&self, n: usize) -> &[MemoryEntry] {
+        &self.entries[..n.min(self.entries.len())]
+    }
+    pub fn unreferenced(&self) -> impl Iterator<Item = &MemoryEntry> {
+        self.entries.iter().filter(|entry| !entry.referenced)
+    }
+    pub fn totals(&self) -> Vec<KindTotal> {
+        ALL_KINDS.iter()
+            .map(|kind| {
+                let mut total = KindTotal { kind: *kind, count: 0, cpu_bytes: 0, gpu_bytes: 0 };
+                for entry in self.entries.iter().filter(|entry| entry.kind == *kind) {
+                    total.count += 1;
+                    total.cpu_bytes += entry.cpu_bytes;
+                    total.gpu_bytes += entry.gpu_bytes;
+                }
+                total
+            })
+            .filter(|total| total.count > 0)
+            .collect()
+    }
+    pub fn cpu_bytes(&self) -> usize {
+        self.entries.iter().map(|entry| entry.cpu_bytes).sum()
+    }
+    pub fn gpu_bytes(&self) -> usize {
+        self.entries.iter().map(|entry| entry.gpu_bytes).sum()
+    }
+    pub fn to_json(&self, top_n: usize) -> String {
+        let report = JsonReport {
+            cpu_bytes: self.cpu_bytes(),
+            gpu_bytes: self.gpu_bytes(),
+            totals: self.totals(),
+            largest: self.top(top_n).iter().collect(),
+            unreferenced: self.unreferenced().collect(),
+        };
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+    pub fn save_json(&self, path: &Path, top_n: usize) -> io::Result<()> {
+        fs::write(path, self.to_json(top_n))
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    cpu_bytes: usize,
+    gpu_bytes: usize,
+    totals: Vec<KindTotal>,
+    largest: Vec<&'a MemoryEntry>,
+    unreferenced: Vec<&'a MemoryEntry>,
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024f64 && unit < UNITS.len() - 1 {
+        value /= 1024f64;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "memory: cpu {}, gpu {}", format_bytes(self.cpu_bytes()), format_bytes(self.gpu_bytes()))?;
+        for total in self.totals().iter() {
+            writeln!(f, "  {:<10} {:>5}  cpu {:>10}  gpu {:>10}", total.kind.label(), total.count, format_bytes(total.cpu_bytes), format_bytes(total.gpu_bytes))?;
+        }
+        writeln!(f, "largest:")?;
+        for entry in self.top(REPORT_TOP_N).iter() {
+            writeln!(f, "  {:<10} {:<24} cpu {:>10}  gpu {:>10}", entry.kind.label(), entry.label(), format_bytes(entry.cpu_bytes), format_bytes(entry.gpu_bytes))?;
+        }
+        let unreferenced: Vec<&MemoryEntry> = self.unreferenced().collect();
+        if !unreferenced.is_empty() {
+            writeln!(f, "unreferenced:")?;
+            for entry in unreferenced.iter() {
+                writeln!(f, "  {:<10} {:<24} {} refs, {}", entry.kind.label(), entry.label(), entry.ref_count, format_bytes(entry.total_bytes()))?;
+            }
+        }
+        Ok(())
+    }
+}
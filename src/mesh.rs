@@ -1,33 +1,248 @@
-use crate::vertex::{Vertex};
+use std::cell::{Ref, RefCell};
 
+use crate::vertex::{Vertex, VertexLayout, PrimitiveMode, BufferUsage, InstanceBuffer, AttributeType};
+use crate::math::Vector3f;
+use crate::boundaries::{BoundingBox, BoundingSphere};
+
+// cpu side mesh without any GL objects, so loaders and tools can build it on any thread
+// empty indices draw the vertices in order
+#[derive(Debug)]
+pub struct MeshData {
+    pub vertices: Vec<f32>,
+    pub indices: Vec<i32>,
+    pub vertex_layout: VertexLayout,
+    pub primitive: PrimitiveMode,
+    // Dynamic and Stream meshes are updated in place instead of re-created
+    pub usage: BufferUsage,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<f32>, indices: Vec<i32>, vertex_layout: VertexLayout) -> Self {
+        MeshData {
+            vertices,
+            indices,
+            vertex_layout,
+            primitive: PrimitiveMode::Triangles,
+            usage: BufferUsage::Static,
+        }
+    }
+    pub fn with_usage(mut self, usage: BufferUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+    pub fn with_primitive(mut self, primitive: PrimitiveMode) -> Self {
+        self.primitive = primitive;
+        self
+    }
+    pub fn is_indexed(&self) -> bool {
+        !self.indices.is_empty()
+    }
+    // as a plain triangle list, strips unrolled keeping the winding, empty for lines and points
+    pub fn triangle_indices(&self) -> Vec<i32> {
+        let elements: Vec<i32> = if self.is_indexed() {
+            self.indices.clone()
+        } else {
+            (0..(self.vertices.len() / self.vertex_layout.get_num_components().max(1)) as i32).collect()
+        };
+        match self.primitive {
+            PrimitiveMode::Triangles => elements,
+            PrimitiveMode::TriangleStrip => {
+                let mut triangles = Vec::new();
+                for i in 2..elements.len() {
+                    if i % 2 == 0 {
+                        triangles.extend_from_slice(&[elements[i - 2], elements[i - 1], elements[i]]);
+                    } else {
+                        triangles.extend_from_slice(&[elements[i - 1], elements[i - 2], elements[i]]);
+                    }
+                }
+                triangles
+            },
+            PrimitiveMode::Lines | PrimitiveMode::LineStrip | PrimitiveMode::Points => Vec::new(),
+        }
+    }
+    pub fn byte_size(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<f32>() + self.indices.len() * std::mem::size_of::<i32>()
+    }
+    // 2D positions lie at z = 0, empty without positions
+    fn positions(&self) -> Vec<Vector3f> {
+        let width = self.vertex_layout.get_num_components();
+        let (offset, is_3d) = match self.vertex_layout.component_offset(&[AttributeType::Position3D, AttributeType::Position2D]) {
+            Some(offset) => (offset, self.vertex_layout.component_offset(&[AttributeType::Position3D]) == Some(offset)),
+            None => return Vec::new(),
+        };
+        self.vertices.chunks_exact(width.max(1))
+            .map(|vertex| Vector3f::new(vertex[offset], vertex[offset + 1], if is_3d { vertex[offset + 2] } else { 0f32 }))
+            .collect()
+    }
+    pub fn bounding_box(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for position in self.positions().iter() {
+            bounds.add_point(position);
+        }
+        bounds
+    }
+    // centered on the bounding box, reaching the farthest position from there
+    pub fn bounding_sphere(&self, bounds: &BoundingBox) -> BoundingSphere {
+        if bounds.is_empty() {
+            return BoundingSphere::new(Vector3f::zero(), 0f32);
+        }
+        let center = bounds.center();
+        let radius = self.positions().iter()
+            .map(|position| Vector3f::sub(position, &center).mag_squared())
+            .fold(0f32, f32::max)
+            .sqrt();
+        BoundingSphere::new(center, radius)
+    }
+}
+
+// cpu side level of detail, built off the GL thread with the mesh
+#[derive(Debug)]
+pub struct LodData {
+    pub data: MeshData,
+    pub screen_size: f32,
+}
+
+// a simplified stand-in, drawn while the mesh covers less than screen_size of the screen's height
+#[derive(Debug)]
+pub struct MeshLod {
+    pub mesh: Mesh,
+    pub screen_size: f32,
+}
+
+/*
+a mesh keeps its cpu data and uploads it the first time it's needed
+after that the GL buffers are reused every frame until unload, or set_data on a static mesh
+levels of detail are meshes of their own, each uploaded the first time it's picked
+*/
 #[derive(Debug)]
 pub struct Mesh {
-    pub vertex: Vertex,
+    data: MeshData,
+    gpu: RefCell<Option<Vertex>>,
+    // largest screen size first
+    lods: Vec<MeshLod>,
+    // local space, worked out once from the cpu data
+    bounding_box: BoundingBox,
+    bounding_sphere: BoundingSphere,
 }
 
 impl Mesh {
-    pub fn new(vertex: Vertex) -> Self {
+    // no GL calls until the mesh is loaded or drawn
+    pub fn new(data: MeshData) -> Self {
+        let bounding_box = data.bounding_box();
         Mesh {
-            vertex,
+            bounding_sphere: data.bounding_sphere(&bounding_box),
+            bounding_box,
+            data,
+            gpu: RefCell::new(None),
+            lods: Vec::new(),
+        }
+    }
+    pub fn with_lods(mut self, lods: Vec<LodData>) -> Self {
+        self.lods = lods.into_iter()
+            .map(|lod| MeshLod { mesh: Mesh::new(lod.data), screen_size: lod.screen_size })
+            .collect();
+        self.lods.sort_by(|a, b| b.screen_size.partial_cmp(&a.screen_size).unwrap());
+        self
+    }
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+    pub fn lods(&self) -> &[MeshLod] {
+        &self.lods
+    }
+    // the coarsest level whose threshold is still above screen_size, the mesh itself when none is
+    pub fn select_lod(&self, screen_size: f32) -> &Mesh {
+        self.lods.iter()
+            .take_while(|lod| screen_size < lod.screen_size)
+            .last()
+            .map_or(self, |lod| &lod.mesh)
+    }
+    pub fn bounding_box(&self) -> &BoundingBox {
+        &self.bounding_box
+    }
+    pub fn bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
+    // dynamic meshes that are resident rewrite their buffers now, otherwise the next load or draw uploads
+    // levels of detail were built from the old data and are dropped
+    pub fn set_data(&mut self, data: MeshData) {
+        self.data = data;
+        self.bounding_box = self.data.bounding_box();
+        self.bounding_sphere = self.data.bounding_sphere(&self.bounding_box);
+        self.lods.clear();
+        match self.gpu.get_mut() {
+            Some(vertex) if self.data.usage != BufferUsage::Static => vertex.update(&self.data),
+            _ => self.unload(),
         }
     }
+    // overwrites vertices starting at first_vertex, same layout, the buffer only gets the changed range
+    // levels of detail and bounds stay as they were, so keep moved vertices inside them
+    pub fn update_vertices(&mut self, first_vertex: usize, vertices: &[f32]) {
+        let width = self.data.vertex_layout.get_num_components();
+        let num_vertices = self.data.vertices.len() / width;
+        assert!(vertices.len().is_multiple_of(width), "update_vertices: {} floats aren't whole vertices of {} floats", vertices.len(), width);
+        assert!(first_vertex + vertices.len() / width <= num_vertices,
+            "update_vertices: vertices {}..{} are past the end of a mesh with {}", first_vertex, first_vertex + vertices.len() / width, num_vertices);
+        let start = first_vertex * width;
+        self.data.vertices[start..start + vertices.len()].copy_from_slice(vertices);
+        if let Some(vertex) = self.gpu.get_mut() {
+            let byte_offset = first_vertex * self.data.vertex_layout.get_stride();
+            vertex.update_vertex_range(byte_offset, &self.data.vertex_layout.pack(vertices));
+        }
+    }
+    pub fn is_resident(&self) -> bool {
+        self.gpu.borrow().is_some()
+    }
+    // levels of detail included
+    pub fn cpu_bytes(&self) -> usize {
+        self.data.byte_size() + self.lods.iter().map(|lod| lod.mesh.cpu_bytes()).sum::<usize>()
+    }
+    // 0 while not resident
+    pub fn gpu_bytes(&self) -> usize {
+        let own = self.gpu.borrow().as_ref().map_or(0, |vertex| vertex.gpu_bytes());
+        own + self.lods.iter().map(|lod| lod.mesh.gpu_bytes()).sum::<usize>()
+    }
+    // uploads once, does nothing while resident
     pub fn load(&self) {
-        self.vertex.buffer_data();
-        self.vertex.set_attrib_pointers();
+        if !self.is_resident() {
+            let vertex = Vertex::new(&self.data);
+            *self.gpu.borrow_mut() = Some(vertex);
+        }
     }
+    // frees the GL buffers of every level, the cpu data stays
     pub fn unload(&self) {
-
+        self.gpu.borrow_mut().take();
+        for lod in self.lods.iter() {
+            lod.mesh.unload();
+        }
+    }
+    fn resident(&self) -> Ref<'_, Vertex> {
+        self.load();
+        Ref::map(self.gpu.borrow(), |gpu| gpu.as_ref().unwrap())
     }
     pub fn bind(&self) {
-        self.vertex.bind();
+        self.resident().bind();
     }
     pub fn unbind(&self) {
-        self.vertex.unbind();
+        if let Some(vertex) = self.gpu.borrow().as_ref() {
+            vertex.unbind();
+        }
     }
     pub fn draw(&self) {
-        self.vertex.draw_call();
+        self.resident().draw_call();
+    }
+    // in indices, or vertices when the mesh has none
+    pub fn draw_range(&self, first: usize, count: usize) {
+        self.resident().draw_range_call(first, count);
     }
     pub fn instanced_draw(&self, num: i32) {
-        self.vertex.instanced_draw_call(num);
+        self.resident().instanced_draw_call(num);
+    }
+    // one instance per entry of the shortest buffer
+    pub fn draw_instances(&self, instances: &[&InstanceBuffer]) {
+        self.resident().draw_instances_call(instances);
     }
-}
\ No newline at end of file
+    pub fn instanced_draw_range(&self, first: usize, count: usize, num: i32) {
+        self.resident().instanced_draw_range_call(first, count, num);
+    }
+}
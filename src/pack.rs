@@ -0,0 +1,328 @@
+/*
+single file asset archive
+shared with build.rs through #[path], so only std and flate2 in here
+
+layout, little endian:
+    magic "LPAK", version u32, entry count u32, index offset u64
+    entry data, back to back
+    index: per entry
+        name length u16, name (utf8, '/' separated like resource names)
+        offset u64, stored size u64, size u64, compression u8, fnv-1a 64 hash of the uncompressed bytes u64
+*/
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression as DeflateLevel;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+pub const MAGIC: [u8; 4] = *b"LPAK";
+pub const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 4 + 4 + 4 + 8;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    BadIndex,
+    EntryNotFound(String),
+    HashMismatch(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io(other)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Deflate,
+}
+
+impl Compression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+        }
+    }
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Deflate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PackEntry {
+    pub name: String,
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub compression: Compression,
+    pub hash: u64,
+}
+
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub struct PackWriter {
+    entries: Vec<(String, Vec<u8>, Compression)>,
+}
+
+impl Default for PackWriter {
+    fn default() -> Self {
+        PackWriter::new()
+    }
+}
+
+impl PackWriter {
+    pub fn new() -> Self {
+        PackWriter {
+            entries: Vec::new(),
+        }
+    }
+    pub fn add(&mut self, name: &str, bytes: Vec<u8>, compression: Compression) {
+        self.entries.push((name.to_string(), bytes, compression));
+    }
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut index: Vec<PackEntry> = Vec::with_capacity(self.entries.len());
+        for (name, bytes, compression) in self.entries.iter() {
+            // before anything is written, so an oversized name doesn't leave half a pack behind
+            name_length(name)?;
+            let stored = match compression {
+                Compression::None => bytes.clone(),
+                Compression::Deflate => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), DeflateLevel::best());
+                    encoder.write_all(bytes)?;
+                    encoder.finish()?
+                }
+            };
+            // not worth it, keep it raw
+            let (stored, compression) = if stored.len() >= bytes.len() {
+                (bytes.clone(), Compression::None)
+            } else {
+                (stored, *compression)
+            };
+            index.push(PackEntry {
+                name: name.clone(),
+                offset: HEADER_SIZE + data.len() as u64,
+                stored_size: stored.len() as u64,
+                size: bytes.len() as u64,
+                compression,
+                hash: fnv1a_64(bytes),
+            });
+            data.extend_from_slice(&stored);
+        }
+
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        file.write_all(&MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&(index.len() as u32).to_le_bytes())?;
+        file.write_all(&(HEADER_SIZE + data.len() as u64).to_le_bytes())?;
+        file.write_all(&data)?;
+        for entry in index.iter() {
+            file.write_all(&name_length(&entry.name)?.to_le_bytes())?;
+            file.write_all(entry.name.as_bytes())?;
+            file.write_all(&entry.offset.to_le_bytes())?;
+            file.write_all(&entry.stored_size.to_le_bytes())?;
+            file.write_all(&entry.size.to_le_bytes())?;
+            file.write_all(&[entry.compression.to_byte()])?;
+            file.write_all(&entry.hash.to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+// the index stores it in a u16
+fn name_length(name: &str) -> io::Result<u16> {
+    u16::try_from(name.len()).map_err(|_| io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("pack entry name is {} bytes, at most {} fit in the index", name.len(), u16::MAX),
+    ))
+}
+
+// only the index is kept in memory, entries are read from disk on demand
+pub struct PackReader {
+    path: PathBuf,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl PackReader {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut file = fs::File::open(path)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let version = read_u32(&header[4..8]);
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let entry_count = read_u32(&header[8..12]);
+        let index_offset = read_u64(&header[12..20]);
+
+        let mut index = Vec::new();
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_to_end(&mut index)?;
+
+        let mut entries = HashMap::with_capacity(entry_count as usize);
+        let mut cursor = 0;
+        for _ in 0..entry_count {
+            let entry = read_entry(&index, &mut cursor).ok_or(Error::BadIndex)?;
+            entries.insert(entry.name.clone(), entry);
+        }
+        Ok(PackReader {
+            path: path.into(),
+            entries,
+        })
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+    pub fn entries(&self) -> impl Iterator<Item = &PackEntry> {
+        self.entries.values()
+    }
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = self.entries.get(name).ok_or_else(|| Error::EntryNotFound(name.to_string()))?;
+
+        let mut file = fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        file.read_exact(&mut stored)?;
+
+        let bytes = match entry.compression {
+            Compression::None => stored,
+            Compression::Deflate => {
+                let mut bytes = Vec::with_capacity(entry.size as usize);
+                DeflateDecoder::new(&stored[..]).read_to_end(&mut bytes)?;
+                bytes
+            }
+        };
+        if bytes.len() as u64 != entry.size || fnv1a_64(&bytes) != entry.hash {
+            return Err(Error::HashMismatch(name.to_string()));
+        }
+        Ok(bytes)
+    }
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&bytes[0..4]);
+    u32::from_le_bytes(b)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&bytes[0..8]);
+    u64::from_le_bytes(b)
+}
+
+fn read_entry(index: &[u8], cursor: &mut usize) -> Option<PackEntry> {
+    let name_len = read_u16(index.get(*cursor..*cursor + 2)?) as usize;
+    *cursor += 2;
+    let name = String::from_utf8(index.get(*cursor..*cursor + name_len)?.to_vec()).ok()?;
+    *cursor += name_len;
+    let fields = index.get(*cursor..*cursor + 8 * 3 + 1 + 8)?;
+    *cursor += fields.len();
+    Some(PackEntry {
+        name,
+        offset: read_u64(&fields[0..8]),
+        stored_size: read_u64(&fields[8..16]),
+        size: read_u64(&fields[16..24]),
+        compression: Compression::from_byte(fields[24])?,
+        hash: read_u64(&fields[25..33]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh file in the temp directory, removed when dropped
+    struct TempPack(PathBuf);
+
+    impl TempPack {
+        fn new(name: &str) -> Self {
+            TempPack(std::env::temp_dir().join(format!("{}_{}.pak", name, std::process::id())))
+        }
+    }
+
+    impl Drop for TempPack {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn compressible() -> Vec<u8> {
+        b"the same line over and over\n".iter().cycle().take(4096).copied().collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pack = TempPack::new("pack_round_trip");
+        let mut writer = PackWriter::new();
+        writer.add("shaders/flat.glsl", compressible(), Compression::Deflate);
+        writer.add("textures/raw.bin", (0..=255u8).collect(), Compression::None);
+        writer.write_to(&pack.0).unwrap();
+
+        let reader = PackReader::open(&pack.0).unwrap();
+        let compressed = reader.entries().find(|e| e.name == "shaders/flat.glsl").unwrap();
+        assert_eq!(compressed.compression, Compression::Deflate);
+        assert!(compressed.stored_size < compressed.size);
+        let stored = reader.entries().find(|e| e.name == "textures/raw.bin").unwrap();
+        assert_eq!(stored.compression, Compression::None);
+        assert_eq!(stored.stored_size, stored.size);
+
+        assert_eq!(reader.read("shaders/flat.glsl").unwrap(), compressible());
+        assert_eq!(reader.read("textures/raw.bin").unwrap(), (0..=255u8).collect::<Vec<u8>>());
+        assert!(matches!(reader.read("missing"), Err(Error::EntryNotFound(_))));
+    }
+
+    #[test]
+    fn hash_mismatch() {
+        let pack = TempPack::new("pack_hash_mismatch");
+        let mut writer = PackWriter::new();
+        writer.add("raw.bin", vec![7u8; 64], Compression::None);
+        writer.write_to(&pack.0).unwrap();
+
+        // flip a byte of the entry's data
+        let mut bytes = fs::read(&pack.0).unwrap();
+        bytes[HEADER_SIZE as usize + 10] ^= 0xff;
+        fs::write(&pack.0, &bytes).unwrap();
+
+        let reader = PackReader::open(&pack.0).unwrap();
+        assert!(matches!(reader.read("raw.bin"), Err(Error::HashMismatch(_))));
+    }
+
+    #[test]
+    fn long_name() {
+        let pack = TempPack::new("pack_long_name");
+        let mut writer = PackWriter::new();
+        writer.add(&"a".repeat(u16::MAX as usize + 1), vec![1u8], Compression::None);
+        assert!(matches!(writer.write_to(&pack.0), Err(Error::Io(_))));
+        assert!(!pack.0.exists());
+    }
+}
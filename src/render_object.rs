@@ -1,30 +1,137 @@
 use crate::transform::Transform;
-use crate::shader::{Program};
+use crate::shader::{Program, AttributeMismatch};
 use crate::mesh::Mesh;
 use crate::camera::Camera;
-use crate::vertex::{Vertex};
-use crate::resources::{ResourceKey, Resources};
+use crate::resources::{self, Handle, Resources, Retained};
 use crate::light::Light;
 use crate::material::{MaterialPropertyType, Material};
+use crate::memory::References;
+use crate::math::Vector4f;
+use crate::boundaries::{BoundingBox, BoundingSphere};
+use crate::vertex::{InstanceBuffer, VertexLayout, AttributeType, FIRST_INSTANCE_LOCATION};
+use std::cell::Cell;
 
+#[derive(Debug)]
+pub enum Error {
+    Resources(resources::Error),
+    // the mesh (or instance buffer) doesn't feed what the program's vertex shader reads
+    LayoutMismatch { program: String, mesh: String, mismatches: Vec<AttributeMismatch> },
+}
+
+impl From<resources::Error> for Error {
+    fn from(other: resources::Error) -> Self {
+        Error::Resources(other)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Resources(error) => write!(f, "{:?}", error),
+            Error::LayoutMismatch { program, mesh, mismatches } => {
+                write!(f, "mesh {} doesn't match program {}:", mesh, program)?;
+                for mismatch in mismatches.iter() {
+                    write!(f, "\n    {}", mismatch)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/*
+checked against the mesh as it is now, which is often the async loader's placeholder,
+so LayoutCheck runs it again on the first draw after the mesh or program is replaced
+*/
+fn check_mesh_layout(program: &Program, program_name: &str, layout: &VertexLayout, mesh_name: &str, first_location: usize, last_location: usize) -> Result<(), Error> {
+    program.check_layout(layout, first_location, last_location)
+        .map_err(|mismatches| Error::LayoutMismatch {
+            program: program_name.to_string(),
+            mesh: mesh_name.to_string(),
+            mismatches,
+        })
+}
+
+// the layout check as of the mesh and program versions it last ran against
+#[derive(Default)]
+struct LayoutCheck {
+    // mesh version, program version, passed
+    checked: Cell<Option<(u32, u32, bool)>>,
+}
+
+impl LayoutCheck {
+    // prints the mismatch once per change instead of drawing garbage every frame
+    fn passes(&self, res: &Resources, program_key: Handle<Program>, mesh_key: Handle<Mesh>) -> bool {
+        let mesh_version = res.get_mesh_version(mesh_key).unwrap();
+        let program_version = res.get_program_version(program_key).unwrap();
+        match self.checked.get() {
+            Some((mesh, program, passed)) if mesh == mesh_version && program == program_version => passed,
+            _ => {
+                let program_name = res.get_program_name(program_key).map_or_else(|| format!("{:?}", program_key), str::to_string);
+                let mesh_name = res.get_mesh_name(mesh_key).map_or_else(|| format!("{:?}", mesh_key), str::to_string);
+                let layout = &res.get_mesh(mesh_key).unwrap().data().vertex_layout;
+                let result = check_mesh_layout(res.get_program(program_key).unwrap(), &program_name, layout, &mesh_name, 0, FIRST_INSTANCE_LOCATION);
+                if let Err(error) = &result {
+                    println!("{}", error);
+                }
+                self.checked.set(Some((mesh_version, program_version, result.is_ok())));
+                result.is_ok()
+            }
+        }
+    }
+}
+
+// keeps its mesh, program and material textures loaded until dropped, swap materials with set_material so new textures are held too
 pub struct RenderObject {
     pub transform: Transform,
-    pub program_key: ResourceKey,
-    pub mesh_key: ResourceKey,
+    pub program_key: Handle<Program>,
+    pub mesh_key: Handle<Mesh>,
     pub material: Material,
+    retained: Retained,
+    layout_check: LayoutCheck,
 }
 
 impl RenderObject {
-    pub fn new(res: &Resources, transform: Transform, program_name: &str, mesh_name: &str, material: Material) -> Self {
+    pub fn new(res: &Resources, transform: Transform, program_name: &str, mesh_name: &str, material: Material) -> Result<Self, Error> {
+        let program_key = res.get_program_id_by_name(program_name)?;
+        let mesh_key = res.get_mesh_id_by_name(mesh_name)?;
+        let layout = &res.get_mesh(mesh_key)?.data().vertex_layout;
+        check_mesh_layout(res.get_program(program_key)?, program_name, layout, mesh_name, 0, FIRST_INSTANCE_LOCATION)?;
 
-        RenderObject {
+        RenderObject::from_keys(res, transform, program_key, mesh_key, material)
+    }
+    // for meshes registered without a name, e.g. by the OBJ and glTF importers
+    pub fn from_keys(res: &Resources, transform: Transform, program_key: Handle<Program>, mesh_key: Handle<Mesh>, material: Material) -> Result<Self, Error> {
+        let mut object = RenderObject {
             transform,
-            program_key: res.get_program_id_by_name(program_name).unwrap(),
-            mesh_key: res.get_mesh_id_by_name(mesh_name).unwrap(),
+            program_key,
+            mesh_key,
             material,
-        }
+            retained: Retained::default(),
+            layout_check: LayoutCheck::default(),
+        };
+        object.retain(res)?;
+        Ok(object)
+    }
+    fn retain(&mut self, res: &Resources) -> Result<(), Error> {
+        let mut references = References::new();
+        self.collect_references(&mut references);
+        self.retained = res.retain(&references)?;
+        Ok(())
+    }
+    pub fn set_material(&mut self, res: &Resources, material: Material) -> Result<(), Error> {
+        self.material = material;
+        self.retain(res)
+    }
+    pub fn collect_references(&self, references: &mut References) {
+        references.programs.insert(self.program_key);
+        references.meshes.insert(self.mesh_key);
+        references.add_material(&self.material);
     }
     pub fn draw(&self, res: &Resources, camera: &Camera) {
+        if !self.layout_check.passes(res, self.program_key, self.mesh_key) {
+            return;
+        }
         let program = res.get_program(self.program_key).unwrap();
         program.set_used();
         program.setMat4fv("proj", camera.proj_mat().as_ptr()).unwrap();
@@ -34,52 +141,131 @@ impl RenderObject {
         program.setMat4fv("model_rot", self.transform.model_rot().as_ptr()).unwrap();
         self.material.load_shader_data(res, &program);
         let mesh = res.get_mesh(self.mesh_key).unwrap();
-        mesh.load();
+        let mesh = mesh.select_lod(self.screen_size(mesh, camera));
         mesh.bind();
         mesh.draw();
         crate::gl_util::gl_dump_errors();
 
     }
+    // fraction of the screen's height the mesh's world bounding sphere covers
+    pub fn screen_size(&self, mesh: &Mesh, camera: &Camera) -> f32 {
+        let sphere = mesh.bounding_sphere().transformed(&self.transform.model_mat());
+        camera.screen_size(&sphere.center, sphere.radius)
+    }
+    // the mesh's bounds through the object's transform, for picking, culling and collision
+    pub fn world_bounding_box(&self, res: &Resources) -> BoundingBox {
+        let mesh = res.get_mesh(self.mesh_key).unwrap();
+        mesh.bounding_box().transformed(&self.transform.model_mat())
+    }
+    pub fn world_bounding_sphere(&self, res: &Resources) -> BoundingSphere {
+        let mesh = res.get_mesh(self.mesh_key).unwrap();
+        mesh.bounding_sphere().transformed(&self.transform.model_mat())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+    pub transform: Transform,
+    // multiplies the material's albedo
+    pub color: Vector4f,
+}
+
+impl Instance {
+    pub fn new(transform: Transform) -> Self {
+        Instance {
+            transform,
+            color: Vector4f::new(1f32, 1f32, 1f32, 1f32),
+        }
+    }
 }
 
-// dumb experiment with instancing
-pub struct TerrainChunkObject {
+/*
+one mesh drawn many times in a single call, each instance with its own transform and color
+the instance buffer is only rewritten after the instances changed
+*/
+pub struct InstancedRenderObject {
     pub transform: Transform,
-    pub program_key: ResourceKey,
-    pub mesh_key: ResourceKey,
+    pub program_key: Handle<Program>,
+    pub mesh_key: Handle<Mesh>,
     pub material: Material,
+    instances: Vec<Instance>,
+    instance_buffer: InstanceBuffer,
+    dirty: Cell<bool>,
+    retained: Retained,
+    layout_check: LayoutCheck,
 }
 
-impl TerrainChunkObject {
-    pub fn new(res: &Resources, transform: Transform, program_name: &str, mesh_name: &str, material: Material) -> Self {
+impl InstancedRenderObject {
+    pub fn new(res: &Resources, transform: Transform, program_name: &str, mesh_name: &str, material: Material, instances: Vec<Instance>) -> Result<Self, Error> {
+        let program_key = res.get_program_id_by_name(program_name)?;
+        let mesh_key = res.get_mesh_id_by_name(mesh_name)?;
+        let program = res.get_program(program_key)?;
+        let layout = &res.get_mesh(mesh_key)?.data().vertex_layout;
+        check_mesh_layout(program, program_name, layout, mesh_name, 0, FIRST_INSTANCE_LOCATION)?;
+        // matches g_instanced_vert.glsl
+        let instance_buffer = InstanceBuffer::new(VertexLayout::new(vec![AttributeType::Mat4, AttributeType::ColorRGBA8]));
+        check_mesh_layout(program, program_name, instance_buffer.layout(), "instances", FIRST_INSTANCE_LOCATION, usize::MAX)?;
 
-        TerrainChunkObject {
+        let mut object = InstancedRenderObject {
             transform,
-            program_key: res.get_program_id_by_name(program_name).unwrap(),
-            mesh_key: res.get_mesh_id_by_name(mesh_name).unwrap(),
+            program_key,
+            mesh_key,
             material,
+            instances,
+            instance_buffer,
+            dirty: Cell::new(true),
+            retained: Retained::default(),
+            layout_check: LayoutCheck::default(),
+        };
+        let mut references = References::new();
+        object.collect_references(&mut references);
+        object.retained = res.retain(&references)?;
+        Ok(object)
+    }
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        self.instances = instances;
+        self.dirty.set(true);
+    }
+    // marks the buffer for a rewrite, so only borrow mutably when actually changing something
+    pub fn instances_mut(&mut self) -> &mut Vec<Instance> {
+        self.dirty.set(true);
+        &mut self.instances
+    }
+    pub fn collect_references(&self, references: &mut References) {
+        references.programs.insert(self.program_key);
+        references.meshes.insert(self.mesh_key);
+        references.add_material(&self.material);
+    }
+    fn upload_instances(&self) {
+        let mut data = Vec::with_capacity(self.instances.len() * self.instance_buffer.layout().get_num_components());
+        for instance in self.instances.iter() {
+            data.extend_from_slice(&instance.transform.model_mat().entries);
+            let c = instance.color;
+            data.extend_from_slice(&[c.x, c.y, c.z, c.w]);
         }
+        self.instance_buffer.set_data(&data);
+        self.dirty.set(false);
     }
     pub fn draw(&self, res: &Resources, camera: &Camera) {
+        if !self.layout_check.passes(res, self.program_key, self.mesh_key) {
+            return;
+        }
+        if self.dirty.get() {
+            self.upload_instances();
+        }
         let program = res.get_program(self.program_key).unwrap();
         program.set_used();
         program.setMat4fv("proj", camera.proj_mat().as_ptr()).unwrap();
         program.setMat4fv("view", camera.view_mat().as_ptr()).unwrap();
         program.setMat4fv("model", self.transform.model_mat().as_ptr()).unwrap();
-
         program.setMat4fv("model_rot", self.transform.model_rot().as_ptr()).unwrap();
-        program.setMat4fv("view_rot", camera.view_rot().as_ptr()).unwrap();
 
-        let numX = 100;
-        let numZ = 500;
-        program.set1i("numX", numX);
-        program.set1i("numZ", numZ);
         self.material.load_shader_data(res, &program);
         let mesh = res.get_mesh(self.mesh_key).unwrap();
-        mesh.load();
-        mesh.bind();
-        mesh.instanced_draw(numX * numZ);
+        mesh.draw_instances(&[&self.instance_buffer]);
         crate::gl_util::gl_dump_errors();
-
     }
-}
\ No newline at end of file
+}
@@ -8,6 +8,8 @@ use crate::game::*;
 use crate::transform::Transform;
 use crate::material::*;
 use crate::math::*;
+use crate::light::MAX_LIGHTS;
+use crate::memory::{MemoryEntry, ResourceKind};
 
 pub struct Renderer {
     gbuffer: GBuffer,
@@ -41,8 +43,8 @@ impl Renderer {
             scene.player.draw(res, camera);
 
 
-            for terrain_chunk in scene.terrain_chunks.iter() {
-                terrain_chunk.draw(res, camera);
+            for obj in scene.instanced_objects.iter() {
+                obj.draw(res, camera);
             }
 
             for target in scene.targets.iter() {
@@ -85,14 +87,14 @@ impl Renderer {
             lightpass_program.set3f("camPos", camera.transform.translation.x, camera.transform.translation.y, camera.transform.translation.z);
 
 
-            lightpass_program.set3f("lightPositions[0]", scene.lights[0].get_position().x, scene.lights[0].get_position().y, scene.lights[0].get_position().z);
-            lightpass_program.set3f("lightColors[0]", scene.lights[0].get_color().x, scene.lights[0].get_color().y, scene.lights[0].get_color().z);
-            lightpass_program.set3f("lightPositions[1]", scene.lights[1].get_position().x, scene.lights[1].get_position().y, scene.lights[1].get_position().z);
-            lightpass_program.set3f("lightColors[1]", scene.lights[1].get_color().x, scene.lights[1].get_color().y, scene.lights[1].get_color().z);
-            lightpass_program.set3f("lightPositions[2]", scene.lights[2].get_position().x, scene.lights[2].get_position().y, scene.lights[2].get_position().z);
-            lightpass_program.set3f("lightColors[2]", scene.lights[2].get_color().x, scene.lights[2].get_color().y, scene.lights[2].get_color().z);
-            lightpass_program.set3f("lightPositions[3]", scene.lights[3].get_position().x, scene.lights[3].get_position().y, scene.lights[3].get_position().z);
-            lightpass_program.set3f("lightColors[3]", scene.lights[3].get_color().x, scene.lights[3].get_color().y, scene.lights[3].get_color().z);
+            let light_count = scene.lights.len().min(MAX_LIGHTS);
+            for (i, light) in scene.lights.iter().take(light_count).enumerate() {
+                let position = light.get_position();
+                let color = light.get_color();
+                lightpass_program.set3f(&format!("lightPositions[{}]", i), position.x, position.y, position.z);
+                lightpass_program.set3f(&format!("lightColors[{}]", i), color.x, color.y, color.z);
+            }
+            lightpass_program.set1i("lightCount", light_count as i32);
             
             self.quad_screen_mesh.draw();
 
@@ -131,4 +133,30 @@ impl Renderer {
             self.quad_screen_mesh.draw();
         }
     }
-}
\ No newline at end of file
+    // objects the renderer owns itself, next to what Resources reports
+    pub fn memory_entries(&self) -> Vec<MemoryEntry> {
+        let mut entries: Vec<MemoryEntry> = self.gbuffer.attachments().iter()
+            .map(|(name, texture)| MemoryEntry {
+                kind: ResourceKind::Attachment,
+                name: Some(format!("gbuffer {}", name)),
+                index: None,
+                cpu_bytes: texture.cpu_bytes(),
+                gpu_bytes: texture.gpu_bytes(),
+                resident: texture.is_resident(),
+                ref_count: 1,
+                referenced: true,
+            })
+            .collect();
+        entries.push(MemoryEntry {
+            kind: ResourceKind::Mesh,
+            name: Some("screen quad".to_string()),
+            index: None,
+            cpu_bytes: self.quad_screen_mesh.cpu_bytes(),
+            gpu_bytes: self.quad_screen_mesh.gpu_bytes(),
+            resident: self.quad_screen_mesh.is_resident(),
+            ref_count: 1,
+            referenced: true,
+        });
+        entries
+    }
+}
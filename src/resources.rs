@@ -1,12 +1,19 @@
 /*
 holds resources
+every kind has its own typed Handle, a handle to a removed resource stops resolving instead of aliasing a new one
+whatever keeps handles around retains them, a released resource is unloaded once the last holder is dropped
 */
 
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::rc::Rc;
 
 use crate::mesh::Mesh;
 use crate::texture::Texture;
 use crate::shader::{Program};
+use crate::material::Material;
+use crate::memory::{MemoryEntry, MemoryReport, References, ResourceKind};
 
 #[derive(Debug)]
 pub enum Error {
@@ -15,46 +22,245 @@ pub enum Error {
     ResourceNameAlreadyExists(String),
 }
 
-pub type ResourceKey = usize;
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+// by hand, derive would want T: Clone etc
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Handle<T> {}
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+impl<T> Eq for Handle<T> {}
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+/*
+references a holder keeps on resources, taken with Resources::retain and given back when dropped
+counts are shared with the slots, so dropping needs no access to Resources
+*/
+#[derive(Default)]
+pub struct Retained {
+    counts: Vec<Rc<Cell<usize>>>,
+}
+
+impl Retained {
+    fn add(&mut self, count: &Rc<Cell<usize>>) {
+        count.set(count.get() + 1);
+        self.counts.push(count.clone());
+    }
+}
+
+impl Clone for Retained {
+    fn clone(&self) -> Self {
+        let mut retained = Retained::default();
+        for count in self.counts.iter() {
+            retained.add(count);
+        }
+        retained
+    }
+}
+
+impl Drop for Retained {
+    fn drop(&mut self) {
+        for count in self.counts.iter() {
+            count.set(count.get().saturating_sub(1));
+        }
+    }
+}
+
+impl std::fmt::Debug for Retained {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Retained({})", self.counts.len())
+    }
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    generation: u32,
+    // the owner's reference while owned plus one per holder
+    ref_count: Rc<Cell<usize>>,
+    owned: bool,
+    // what the resource itself holds on to, e.g. a material's textures
+    dependencies: Retained,
+    // bumped by every replace, so holders can tell the resource changed under the same handle
+    version: u32,
+    name: Option<String>,
+}
 
 struct ResourceEntries<T> {
-    entries: Vec<T>,
-    name_map: HashMap<&'static str, ResourceKey>,
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    name_map: HashMap<String, Handle<T>>,
 }
 impl<T> ResourceEntries<T> {
     pub fn new() -> Self {
         ResourceEntries {
-            entries: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
             name_map: HashMap::new(),
         }
     }
-    pub fn add_resource(&mut self, r: T) -> ResourceKey {
-        self.entries.push(r);
-        self.entries.len() - 1
+    // starts with one reference, owned by whoever added it until they release it
+    pub fn add_resource(&mut self, r: T) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => {
+                // a fresh count, holders of the old resource may still drop theirs
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(r);
+                slot.ref_count = Rc::new(Cell::new(1));
+                slot.owned = true;
+                slot.version = 0;
+                index
+            },
+            None => {
+                self.slots.push(Slot {
+                    value: Some(r),
+                    generation: 0,
+                    ref_count: Rc::new(Cell::new(1)),
+                    owned: true,
+                    dependencies: Retained::default(),
+                    version: 0,
+                    name: None,
+                });
+                (self.slots.len() - 1) as u32
+            },
+        };
+        Handle { index, generation: self.slots[index as usize].generation, marker: PhantomData }
     }
-    pub fn add_resource_by_name(&mut self, name: &'static str, res: T) -> Result<ResourceKey, Error> {
+    pub fn add_resource_by_name(&mut self, name: &str, res: T) -> Result<Handle<T>, Error> {
         if self.name_map.contains_key(name) {
             return Err(Error::ResourceNameAlreadyExists(name.to_string()));
         }
-        let id = self.add_resource(res);
-        self.name_map.insert(name, id);
-        Ok(id)
+        let handle = self.add_resource(res);
+        self.slots[handle.index as usize].name = Some(name.to_string());
+        self.name_map.insert(name.to_string(), handle);
+        Ok(handle)
     }
-    pub fn get_resource(&self, k: ResourceKey) -> Result<&T, Error> {
-        self.entries.get(k).ok_or(Error::ResourceDoesNotExist)
+    fn slot(&self, h: Handle<T>) -> Result<&Slot<T>, Error> {
+        match self.slots.get(h.index as usize) {
+            Some(slot) if slot.generation == h.generation && slot.value.is_some() => Ok(slot),
+            _ => Err(Error::ResourceDoesNotExist),
+        }
     }
-    pub fn get_resource_by_name(&self, name: &str) -> Result<&T, Error> {
-        if !self.name_map.contains_key(name) {
-            return Err(Error::ResourceNameDoesNotExist(name.to_string()));
+    fn slot_mut(&mut self, h: Handle<T>) -> Result<&mut Slot<T>, Error> {
+        match self.slots.get_mut(h.index as usize) {
+            Some(slot) if slot.generation == h.generation && slot.value.is_some() => Ok(slot),
+            _ => Err(Error::ResourceDoesNotExist),
+        }
+    }
+    // swaps the value behind an existing handle, the old one is dropped
+    pub fn replace_resource(&mut self, h: Handle<T>, res: T) -> Result<(), Error> {
+        let slot = self.slot_mut(h)?;
+        slot.value = Some(res);
+        slot.version = slot.version.wrapping_add(1);
+        Ok(())
+    }
+    pub fn version(&self, h: Handle<T>) -> Result<u32, Error> {
+        Ok(self.slot(h)?.version)
+    }
+    pub fn name(&self, h: Handle<T>) -> Option<&str> {
+        self.slot(h).ok()?.name.as_deref()
+    }
+    // drops the resource now whatever its reference count, the handle and its name stop resolving
+    pub fn remove_resource(&mut self, h: Handle<T>) -> Result<T, Error> {
+        let slot = self.slot_mut(h)?;
+        let value = slot.value.take().unwrap();
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.owned = false;
+        slot.dependencies = Retained::default();
+        if let Some(name) = slot.name.take() {
+            self.name_map.remove(&name);
+        }
+        self.free.push(h.index);
+        Ok(value)
+    }
+    pub fn retain_resource(&self, h: Handle<T>, retained: &mut Retained) -> Result<(), Error> {
+        retained.add(&self.slot(h)?.ref_count);
+        Ok(())
+    }
+    fn set_dependencies(&mut self, h: Handle<T>, dependencies: Retained) {
+        self.slots[h.index as usize].dependencies = dependencies;
+    }
+    /*
+    gives up the owner's reference, unloads right away when nothing else holds the resource, true if it did
+    otherwise remove_released unloads it after the last holder is dropped, releasing twice does nothing
+    */
+    pub fn release_resource(&mut self, h: Handle<T>) -> Result<bool, Error> {
+        let slot = self.slot_mut(h)?;
+        if !slot.owned {
+            return Ok(false);
+        }
+        slot.owned = false;
+        slot.ref_count.set(slot.ref_count.get().saturating_sub(1));
+        if slot.ref_count.get() == 0 {
+            self.remove_resource(h)?;
+            return Ok(true);
         }
-        let id = *self.name_map.get(name).unwrap();
-        self.entries.get(id).ok_or(Error::ResourceDoesNotExist)
+        Ok(false)
     }
-    pub fn get_resource_id_by_name(&self, name: &str) -> Result<ResourceKey, Error> {
-        if !self.name_map.contains_key(name) {
-            return Err(Error::ResourceNameDoesNotExist(name.to_string()));
+    // released resources whose holders are all gone, the number removed
+    pub fn remove_released(&mut self) -> usize {
+        let released: Vec<Handle<T>> = self.iter()
+            .filter(|(_, slot, _)| !slot.owned && slot.ref_count.get() == 0)
+            .map(|(handle, _, _)| handle)
+            .collect();
+        for handle in released.iter() {
+            self.remove_resource(*handle).unwrap();
         }
-        Ok(*self.name_map.get(name).unwrap())
+        released.len()
+    }
+    pub fn get_resource(&self, h: Handle<T>) -> Result<&T, Error> {
+        Ok(self.slot(h)?.value.as_ref().unwrap())
+    }
+    pub fn get_resource_by_name(&self, name: &str) -> Result<&T, Error> {
+        self.get_resource(self.get_resource_id_by_name(name)?)
+    }
+    pub fn get_resource_id_by_name(&self, name: &str) -> Result<Handle<T>, Error> {
+        self.name_map.get(name).copied().ok_or(Error::ResourceNameDoesNotExist(name.to_string()))
+    }
+    fn iter(&self) -> impl Iterator<Item = (Handle<T>, &Slot<T>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = Handle { index: index as u32, generation: slot.generation, marker: PhantomData };
+            slot.value.as_ref().map(|value| (handle, slot, value))
+        })
+    }
+    // sizes gives (cpu bytes, gpu bytes, resident)
+    fn memory_entries<F>(&self, kind: ResourceKind, referenced: &HashSet<Handle<T>>, sizes: F) -> Vec<MemoryEntry>
+        where F: Fn(&T) -> (usize, usize, bool) {
+        self.iter()
+            .map(|(handle, slot, value)| {
+                let (cpu_bytes, gpu_bytes, resident) = sizes(value);
+                MemoryEntry {
+                    kind,
+                    name: slot.name.clone(),
+                    index: Some(handle.index),
+                    cpu_bytes,
+                    gpu_bytes,
+                    resident,
+                    ref_count: slot.ref_count.get(),
+                    referenced: referenced.contains(&handle),
+                }
+            })
+            .collect()
     }
 }
 
@@ -62,6 +268,7 @@ pub struct Resources {
     meshes: ResourceEntries<Mesh>,
     textures: ResourceEntries<Texture>,
     programs: ResourceEntries<Program>,
+    materials: ResourceEntries<Material>,
 }
 
 impl Resources {
@@ -70,51 +277,177 @@ impl Resources {
             meshes: ResourceEntries::new(),
             textures: ResourceEntries::new(),
             programs: ResourceEntries::new(),
+            materials: ResourceEntries::new(),
         }
     }
-    pub fn add_mesh(&mut self, mesh: Mesh) -> ResourceKey {
+    pub fn add_mesh(&mut self, mesh: Mesh) -> Handle<Mesh> {
         self.meshes.add_resource(mesh)
     }
-    pub fn add_mesh_by_name(&mut self, name: &'static str, mesh: Mesh) -> Result<ResourceKey, Error> {
+    pub fn add_mesh_by_name(&mut self, name: &str, mesh: Mesh) -> Result<Handle<Mesh>, Error> {
         self.meshes.add_resource_by_name(name, mesh)
-    }   
-    pub fn get_mesh(&self, id: ResourceKey) -> Result<&Mesh, Error> {
+    }
+    pub fn replace_mesh(&mut self, id: Handle<Mesh>, mesh: Mesh) -> Result<(), Error> {
+        self.meshes.replace_resource(id, mesh)
+    }
+    pub fn remove_mesh(&mut self, id: Handle<Mesh>) -> Result<Mesh, Error> {
+        self.meshes.remove_resource(id)
+    }
+    pub fn release_mesh(&mut self, id: Handle<Mesh>) -> Result<bool, Error> {
+        self.meshes.release_resource(id)
+    }
+    pub fn get_mesh(&self, id: Handle<Mesh>) -> Result<&Mesh, Error> {
         self.meshes.get_resource(id)
     }
+    // changes whenever the mesh behind the handle is replaced, e.g. by the async loader or hot reload
+    pub fn get_mesh_version(&self, id: Handle<Mesh>) -> Result<u32, Error> {
+        self.meshes.version(id)
+    }
+    pub fn get_mesh_name(&self, id: Handle<Mesh>) -> Option<&str> {
+        self.meshes.name(id)
+    }
     pub fn get_mesh_by_name(&self, name: &str) -> Result<&Mesh, Error> {
         self.meshes.get_resource_by_name(name)
     }
-    pub fn get_mesh_id_by_name(&self, name: &str) -> Result<ResourceKey, Error> {
+    pub fn get_mesh_id_by_name(&self, name: &str) -> Result<Handle<Mesh>, Error> {
         self.meshes.get_resource_id_by_name(name)
     }
-    pub fn add_texture(&mut self, texture: Texture) -> ResourceKey {
+    pub fn add_texture(&mut self, texture: Texture) -> Handle<Texture> {
         self.textures.add_resource(texture)
     }
-    pub fn add_texture_by_name(&mut self, name: &'static str, texture: Texture) -> Result<ResourceKey, Error> {
+    pub fn add_texture_by_name(&mut self, name: &str, texture: Texture) -> Result<Handle<Texture>, Error> {
         self.textures.add_resource_by_name(name, texture)
-    }   
-    pub fn get_texture(&self, id: ResourceKey) -> Result<&Texture, Error> {
+    }
+    pub fn replace_texture(&mut self, id: Handle<Texture>, texture: Texture) -> Result<(), Error> {
+        self.textures.replace_resource(id, texture)
+    }
+    pub fn remove_texture(&mut self, id: Handle<Texture>) -> Result<Texture, Error> {
+        self.textures.remove_resource(id)
+    }
+    pub fn release_texture(&mut self, id: Handle<Texture>) -> Result<bool, Error> {
+        self.textures.release_resource(id)
+    }
+    pub fn get_texture(&self, id: Handle<Texture>) -> Result<&Texture, Error> {
         self.textures.get_resource(id)
     }
     pub fn get_texture_by_name(&self, name: &str) -> Result<&Texture, Error> {
         self.textures.get_resource_by_name(name)
     }
-    pub fn get_texture_id_by_name(&self, name: &str) -> Result<ResourceKey, Error> {
+    pub fn get_texture_id_by_name(&self, name: &str) -> Result<Handle<Texture>, Error> {
         self.textures.get_resource_id_by_name(name)
     }
-    pub fn add_program(&mut self, program: Program) -> ResourceKey {
+    pub fn add_program(&mut self, program: Program) -> Handle<Program> {
         self.programs.add_resource(program)
     }
-    pub fn add_program_by_name(&mut self, name: &'static str, program: Program) -> Result<ResourceKey, Error> {
+    pub fn add_program_by_name(&mut self, name: &str, program: Program) -> Result<Handle<Program>, Error> {
         self.programs.add_resource_by_name(name, program)
-    }   
-    pub fn get_program(&self, id: ResourceKey) -> Result<&Program, Error> {
+    }
+    pub fn replace_program(&mut self, id: Handle<Program>, program: Program) -> Result<(), Error> {
+        self.programs.replace_resource(id, program)
+    }
+    pub fn remove_program(&mut self, id: Handle<Program>) -> Result<Program, Error> {
+        self.programs.remove_resource(id)
+    }
+    pub fn release_program(&mut self, id: Handle<Program>) -> Result<bool, Error> {
+        self.programs.release_resource(id)
+    }
+    pub fn get_program(&self, id: Handle<Program>) -> Result<&Program, Error> {
         self.programs.get_resource(id)
     }
+    pub fn get_program_version(&self, id: Handle<Program>) -> Result<u32, Error> {
+        self.programs.version(id)
+    }
+    pub fn get_program_name(&self, id: Handle<Program>) -> Option<&str> {
+        self.programs.name(id)
+    }
     pub fn get_program_by_name(&self, name: &str) -> Result<&Program, Error> {
         self.programs.get_resource_by_name(name)
     }
-    pub fn get_program_id_by_name(&self, name: &str) -> Result<ResourceKey, Error> {
+    pub fn get_program_id_by_name(&self, name: &str) -> Result<Handle<Program>, Error> {
         self.programs.get_resource_id_by_name(name)
     }
-}
\ No newline at end of file
+    // textures that are already gone are skipped, drawing with the material reports them
+    fn material_textures(&self, material: &Material) -> Retained {
+        let mut references = References::new();
+        references.add_material(material);
+        let mut retained = Retained::default();
+        for key in references.textures.iter() {
+            let _ = self.textures.retain_resource(*key, &mut retained);
+        }
+        retained
+    }
+    // a material holds on to its textures for as long as it's here
+    pub fn add_material(&mut self, material: Material) -> Handle<Material> {
+        let textures = self.material_textures(&material);
+        let handle = self.materials.add_resource(material);
+        self.materials.set_dependencies(handle, textures);
+        handle
+    }
+    pub fn add_material_by_name(&mut self, name: &str, material: Material) -> Result<Handle<Material>, Error> {
+        let textures = self.material_textures(&material);
+        let handle = self.materials.add_resource_by_name(name, material)?;
+        self.materials.set_dependencies(handle, textures);
+        Ok(handle)
+    }
+    pub fn replace_material(&mut self, id: Handle<Material>, material: Material) -> Result<(), Error> {
+        let textures = self.material_textures(&material);
+        self.materials.replace_resource(id, material)?;
+        self.materials.set_dependencies(id, textures);
+        Ok(())
+    }
+    pub fn remove_material(&mut self, id: Handle<Material>) -> Result<Material, Error> {
+        self.materials.remove_resource(id)
+    }
+    pub fn release_material(&mut self, id: Handle<Material>) -> Result<bool, Error> {
+        self.materials.release_resource(id)
+    }
+    pub fn get_material(&self, id: Handle<Material>) -> Result<&Material, Error> {
+        self.materials.get_resource(id)
+    }
+    pub fn get_material_by_name(&self, name: &str) -> Result<&Material, Error> {
+        self.materials.get_resource_by_name(name)
+    }
+    pub fn get_material_id_by_name(&self, name: &str) -> Result<Handle<Material>, Error> {
+        self.materials.get_resource_id_by_name(name)
+    }
+    // keeps everything in references loaded until the result is dropped, fails on the first handle that's gone
+    pub fn retain(&self, references: &References) -> Result<Retained, Error> {
+        let mut retained = Retained::default();
+        for key in references.meshes.iter() {
+            self.meshes.retain_resource(*key, &mut retained)?;
+        }
+        for key in references.textures.iter() {
+            self.textures.retain_resource(*key, &mut retained)?;
+        }
+        for key in references.programs.iter() {
+            self.programs.retain_resource(*key, &mut retained)?;
+        }
+        for key in references.materials.iter() {
+            self.materials.retain_resource(*key, &mut retained)?;
+        }
+        Ok(retained)
+    }
+    // unloads released resources nothing holds anymore, materials first so their textures follow in the same call
+    pub fn remove_released(&mut self) -> usize {
+        self.materials.remove_released()
+            + self.meshes.remove_released()
+            + self.textures.remove_released()
+            + self.programs.remove_released()
+    }
+    // references are what the scene uses, textures used by materials in here are added on top
+    pub fn memory_report(&self, references: &References) -> MemoryReport {
+        let mut references = references.clone();
+        for (_, _, material) in self.materials.iter() {
+            references.add_material(material);
+        }
+        let mut entries = Vec::new();
+        entries.extend(self.meshes.memory_entries(ResourceKind::Mesh, &references.meshes,
+            |mesh| (mesh.cpu_bytes(), mesh.gpu_bytes(), mesh.is_resident())));
+        entries.extend(self.textures.memory_entries(ResourceKind::Texture, &references.textures,
+            |texture| (texture.cpu_bytes(), texture.gpu_bytes(), texture.is_resident())));
+        entries.extend(self.programs.memory_entries(ResourceKind::Program, &references.programs,
+            |program| (0, program.binary_bytes, true)));
+        entries.extend(self.materials.memory_entries(ResourceKind::Material, &references.materials,
+            |_| (std::mem::size_of::<Material>(), 0, false)));
+        MemoryReport::new(entries)
+    }
+}
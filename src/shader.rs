@@ -5,17 +5,38 @@ https://github.com/Nercury/rust-and-opengl-lessons
 use gl;
 use std;
 use std::ffi::{CString, CStr};
+use crate::loader::{self, FilePath};
+use crate::light::MAX_LIGHTS;
+use crate::vertex::VertexLayout;
 
 #[derive(Debug)]
 pub enum Error {
     CanNotDetermineShaderTypeForResource { name: String },
     CompileError { name: String, message: String },
     LinkError { name: String, message: String },
+    Load { name: String, error: loader::Error },
+    BadInclude { name: String, line: usize },
+    IncludeCycle { chain: Vec<String> },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::CanNotDetermineShaderTypeForResource { name } => write!(f, "can not determine shader type for {}", name),
+            Error::CompileError { name, message } => write!(f, "{}:\n{}", name, message),
+            Error::LinkError { name, message } => write!(f, "{}:\n{}", name, message),
+            Error::Load { name, error } => write!(f, "can not load {}: {:?}", name, error),
+            Error::BadInclude { name, line } => write!(f, "{}:{}: malformed #include", name, line),
+            Error::IncludeCycle { chain } => write!(f, "include cycle: {}", chain.join(" -> ")),
+        }
+    }
 }
 
 
 pub struct Shader {
     id: gl::types::GLuint,
+    // every file that went into the source, includes too
+    files: Vec<String>,
 }
 
 impl Shader {
@@ -24,7 +45,28 @@ impl Shader {
         kind: gl::types::GLenum
     ) -> Result<Shader, Error> {
         let id = shader_from_source(source, kind)?;
-        Ok(Shader { id })
+        Ok(Shader { id, files: Vec::new() })
+    }
+
+    // preprocessed, compile logs point at the original file and line
+    pub fn from_file(root_path: &FilePath, name: &str, kind: gl::types::GLenum) -> Result<Shader, Error> {
+        let preprocessed = preprocess(root_path, name, &engine_defines())?;
+        let id = shader_from_source(&preprocessed.source, kind).map_err(|error| match error {
+            Error::CompileError { message, .. } => Error::CompileError {
+                name: name.to_string(),
+                message: remap_log(&message, &preprocessed.files),
+            },
+            error => error,
+        })?;
+        Ok(Shader { id, files: preprocessed.files })
+    }
+
+    pub fn from_vert_file(root_path: &FilePath, name: &str) -> Result<Shader, Error> {
+        Shader::from_file(root_path, name, gl::VERTEX_SHADER)
+    }
+
+    pub fn from_frag_file(root_path: &FilePath, name: &str) -> Result<Shader, Error> {
+        Shader::from_file(root_path, name, gl::FRAGMENT_SHADER)
     }
 
     pub fn from_vert_source(source: &CStr) -> Result<Shader, Error> {
@@ -87,6 +129,11 @@ fn shader_from_source(
 
 pub struct Program {
     pub id: gl::types::GLuint,
+    pub source_files: Vec<String>,
+    // size of the linked binary as the driver reports it, 0 if it doesn't
+    pub binary_bytes: usize,
+    // active vertex inputs, sorted by location
+    pub attributes: Vec<ProgramAttribute>,
 }
 
 impl Program {
@@ -124,13 +171,65 @@ impl Program {
 
             return Err(Error::LinkError {name: "Link error".to_string(), message: error.to_string_lossy().into_owned() });
         }
-        return Ok( Program { id: program_id } );
+        let mut source_files = shader1.files.clone();
+        for file in shader2.files.iter() {
+            if !source_files.contains(file) {
+                source_files.push(file.clone());
+            }
+        }
+        let mut binary_bytes: gl::types::GLint = 0;
+        unsafe {
+            gl::GetProgramiv(program_id, gl::PROGRAM_BINARY_LENGTH, &mut binary_bytes);
+            // GL 4.1 / ARB_get_program_binary, drop the invalid enum on drivers without it
+            if binary_bytes == 0 {
+                gl::GetError();
+            }
+        }
+        let attributes = reflect_attributes(program_id);
+        Ok( Program { id: program_id, source_files, binary_bytes: binary_bytes.max(0) as usize, attributes } )
     }
     pub fn set_used(&self) {
         unsafe {
             gl::UseProgram(self.id);
         }
     }
+    /*
+    every active input at or above first_location has to come from the layout with the same component count
+    and float/integer kind, layout attributes the shader doesn't read are fine
+    inputs below first_location, or past the layout's locations, belong to someone else, pass the right range
+    */
+    pub fn check_layout(&self, layout: &VertexLayout, first_location: usize, last_location: usize) -> Result<(), Vec<AttributeMismatch>> {
+        let provided = layout.locations(first_location);
+        let mut mismatches = Vec::new();
+        for attribute in self.attributes.iter() {
+            for column in 0..attribute.locations {
+                let location = attribute.location + column;
+                if location < first_location || location >= last_location {
+                    continue;
+                }
+                match provided.iter().find(|p| p.0 == location) {
+                    None => mismatches.push(AttributeMismatch::Missing { name: attribute.name.clone(), location }),
+                    Some(&(_, components, integer)) => {
+                        if components != attribute.components {
+                            mismatches.push(AttributeMismatch::ComponentCount {
+                                name: attribute.name.clone(), location, shader: attribute.components, layout: components,
+                            });
+                        }
+                        if integer != attribute.integer {
+                            mismatches.push(AttributeMismatch::Kind {
+                                name: attribute.name.clone(), location, shader_integer: attribute.integer, layout_integer: integer,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
     pub fn setMat4fv(&self, name: &str, data: *const f32) -> Option<()> {
         unsafe {
             let loc_u = gl::GetUniformLocation(self.id, std::ffi::CString::new(name).unwrap().as_ptr() as *const gl::types::GLchar);
@@ -227,6 +326,100 @@ impl Drop for Program {
 
 
 
+/*
+attribute reflection
+*/
+#[derive(Debug, Clone)]
+pub struct ProgramAttribute {
+    pub name: String,
+    pub location: usize,
+    pub gl_type: gl::types::GLenum,
+    // per location, a mat4 is 4 locations of 4 components
+    pub components: usize,
+    pub locations: usize,
+    // int/uint inputs, fed by glVertexAttribIPointer
+    pub integer: bool,
+}
+
+#[derive(Debug)]
+pub enum AttributeMismatch {
+    Missing { name: String, location: usize },
+    ComponentCount { name: String, location: usize, shader: usize, layout: usize },
+    Kind { name: String, location: usize, shader_integer: bool, layout_integer: bool },
+}
+
+fn kind_name(integer: bool) -> &'static str {
+    if integer { "integer" } else { "float" }
+}
+
+impl std::fmt::Display for AttributeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AttributeMismatch::Missing { name, location } =>
+                write!(f, "{} (location {}) is not in the vertex layout", name, location),
+            AttributeMismatch::ComponentCount { name, location, shader, layout } =>
+                write!(f, "{} (location {}) has {} components in the shader, {} in the vertex layout", name, location, shader, layout),
+            AttributeMismatch::Kind { name, location, shader_integer, layout_integer } =>
+                write!(f, "{} (location {}) is {} in the shader, {} in the vertex layout", name, location, kind_name(*shader_integer), kind_name(*layout_integer)),
+        }
+    }
+}
+
+// (components, locations, integer), doubles are left out, GL 3.3 can't feed them anyway
+fn attribute_type_shape(gl_type: gl::types::GLenum) -> (usize, usize, bool) {
+    match gl_type {
+        gl::FLOAT => (1, 1, false),
+        gl::FLOAT_VEC2 => (2, 1, false),
+        gl::FLOAT_VEC3 => (3, 1, false),
+        gl::FLOAT_VEC4 => (4, 1, false),
+        gl::FLOAT_MAT2 => (2, 2, false),
+        gl::FLOAT_MAT3 => (3, 3, false),
+        gl::FLOAT_MAT4 => (4, 4, false),
+        gl::INT | gl::UNSIGNED_INT => (1, 1, true),
+        gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (2, 1, true),
+        gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (3, 1, true),
+        gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => (4, 1, true),
+        _ => (4, 1, false),
+    }
+}
+
+fn reflect_attributes(program_id: gl::types::GLuint) -> Vec<ProgramAttribute> {
+    let mut count: gl::types::GLint = 0;
+    let mut max_len: gl::types::GLint = 0;
+    unsafe {
+        gl::GetProgramiv(program_id, gl::ACTIVE_ATTRIBUTES, &mut count);
+        gl::GetProgramiv(program_id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_len);
+    }
+    let mut attributes = Vec::new();
+    for i in 0..count {
+        let mut name_buffer = vec![0u8; max_len.max(1) as usize];
+        let mut len: gl::types::GLsizei = 0;
+        let mut size: gl::types::GLint = 0;
+        let mut gl_type: gl::types::GLenum = 0;
+        let location = unsafe {
+            gl::GetActiveAttrib(program_id, i as gl::types::GLuint, max_len, &mut len, &mut size, &mut gl_type, name_buffer.as_mut_ptr() as *mut gl::types::GLchar);
+            gl::GetAttribLocation(program_id, name_buffer.as_ptr() as *const gl::types::GLchar)
+        };
+        // built-ins like gl_VertexID have no location
+        if location < 0 {
+            continue;
+        }
+        name_buffer.truncate(len as usize);
+        let (components, locations, integer) = attribute_type_shape(gl_type);
+        attributes.push(ProgramAttribute {
+            name: String::from_utf8_lossy(&name_buffer).into_owned(),
+            location: location as usize,
+            gl_type,
+            components,
+            // arrays take one slot per element
+            locations: locations * size.max(1) as usize,
+            integer,
+        });
+    }
+    attributes.sort_by_key(|attribute| attribute.location);
+    attributes
+}
+
 fn create_whitespace_cstring_with_len(len: usize) -> CString {
     // allocate buffer of correct size
     let mut buffer: Vec<u8> = Vec::with_capacity(len + 1);
@@ -234,4 +427,126 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
     buffer.extend([b' '].iter().cycle().take(len));
     // convert buffer to CString
     unsafe { CString::from_vec_unchecked(buffer) }
-}
\ No newline at end of file
+}
+
+/*
+GLSL preprocessing
+#include "file" is resolved against the asset root, each file is pasted in at most once
+engine defines go right after #version, #line keeps every file under its own source string number
+*/
+
+pub fn engine_defines() -> Vec<(&'static str, String)> {
+    vec![
+        ("MAX_LIGHTS", MAX_LIGHTS.to_string()),
+    ]
+}
+
+pub struct PreprocessedSource {
+    pub source: CString,
+    // indexed by the source string number used in #line
+    pub files: Vec<String>,
+}
+
+pub fn preprocess(root_path: &FilePath, name: &str, defines: &[(&str, String)]) -> Result<PreprocessedSource, Error> {
+    let mut files = Vec::new();
+    let mut stack = Vec::new();
+    let mut out = String::new();
+    expand_file(root_path, name, defines, &mut files, &mut stack, &mut out)?;
+    let source = CString::new(out).map_err(|_| Error::Load { name: name.to_string(), error: loader::Error::FileContainsNil })?;
+    Ok(PreprocessedSource { source, files })
+}
+
+fn expand_file(root_path: &FilePath, name: &str, defines: &[(&str, String)], files: &mut Vec<String>, stack: &mut Vec<String>, out: &mut String) -> Result<(), Error> {
+    if stack.iter().any(|file| file == name) {
+        let mut chain = stack.clone();
+        chain.push(name.to_string());
+        return Err(Error::IncludeCycle { chain });
+    }
+    let source = loader::load_string(root_path, name)
+        .map_err(|error| Error::Load { name: name.to_string(), error })?;
+    let id = files.len();
+    files.push(name.to_string());
+    stack.push(name.to_string());
+
+    let is_root = stack.len() == 1;
+    let has_version = source.lines().next().is_some_and(|line| line.trim_start().starts_with("#version"));
+    // #version has to stay the first line, the defines follow it
+    if !(is_root && has_version) {
+        if is_root {
+            write_defines(defines, out);
+        }
+        out.push_str(&format!("#line 1 {}\n", id));
+    }
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if i == 0 && is_root && has_version {
+            out.push_str(line);
+            out.push('\n');
+            write_defines(defines, out);
+            out.push_str(&format!("#line 2 {}\n", id));
+        } else if trimmed.starts_with("#include") {
+            let include = parse_include(trimmed).ok_or(Error::BadInclude { name: name.to_string(), line: i + 1 })?;
+            if !files.contains(&include) || stack.contains(&include) {
+                expand_file(root_path, &include, defines, files, stack, out)?;
+            }
+            out.push_str(&format!("#line {} {}\n", i + 2, id));
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn write_defines(defines: &[(&str, String)], out: &mut String) {
+    for (name, value) in defines.iter() {
+        out.push_str(&format!("#define {} {}\n", name, value));
+    }
+}
+
+fn parse_include(line: &str) -> Option<String> {
+    let rest = line["#include".len()..].trim();
+    if rest.len() < 2 || !rest.starts_with('"') {
+        return None;
+    }
+    let end = rest[1..].find('"')? + 1;
+    Some(rest[1..end].to_string())
+}
+
+// drivers print "0(12)", "0:12" or "ERROR: 0:12:", the leading number is the source string
+fn remap_log(log: &str, files: &[String]) -> String {
+    let mut remapped = String::with_capacity(log.len());
+    for line in log.lines() {
+        remapped.push_str(&remap_log_line(line, files));
+        remapped.push('\n');
+    }
+    remapped
+}
+
+fn remap_log_line(line: &str, files: &[String]) -> String {
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        if bytes[start].is_ascii_digit() && (start == 0 || !bytes[start - 1].is_ascii_alphanumeric()) {
+            let mut end = start;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+            let followed_by_line = end + 1 < bytes.len()
+                && (bytes[end] == b':' || bytes[end] == b'(')
+                && bytes[end + 1].is_ascii_digit();
+            if followed_by_line {
+                if let Some(file) = line[start..end].parse::<usize>().ok().and_then(|id| files.get(id)) {
+                    return format!("{}{}{}", &line[..start], file, &line[end..]);
+                }
+                return line.to_string();
+            }
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    line.to_string()
+}
@@ -0,0 +1,155 @@
+/*
+ring buffer for geometry that changes every frame: particles, debug lines, HUD quads
+the buffer is split into one region per frame in flight, writes go into the current region and
+end_frame fences it, a region is only reused once the GPU is past its fence, so nothing is orphaned or reallocated
+
+with GL 4.4 / ARB_buffer_storage the whole buffer is mapped once and stays mapped (persistent + coherent),
+on plain 3.3 every write maps just its range unsynchronized, which the fences make safe
+*/
+
+use std::ptr;
+
+use crate::vertex::{VertexArray, VertexLayout};
+use crate::gl_util::{gl_version, has_extension};
+
+const FRAMES_IN_FLIGHT: usize = 3;
+// a region still busy after this long is written anyway, nanoseconds
+const FENCE_TIMEOUT: u64 = 1_000_000_000;
+
+// core in 4.4, the game asks for a 3.3 context so it usually comes from the extension
+fn has_buffer_storage() -> bool {
+    gl::BufferStorage::is_loaded() && (gl_version() >= (4, 4) || has_extension("GL_ARB_buffer_storage"))
+}
+
+pub struct StreamBuffer {
+    id: gl::types::GLuint,
+    target: gl::types::GLenum,
+    region_size: usize,
+    region: usize,
+    // bytes written to the current region
+    head: usize,
+    fences: [gl::types::GLsync; FRAMES_IN_FLIGHT],
+    // null without buffer storage
+    mapped: *mut u8,
+}
+
+impl StreamBuffer {
+    // frame_size is how much can be written between two end_frame calls
+    pub fn new(target: gl::types::GLenum, frame_size: usize) -> Self {
+        let size = frame_size * FRAMES_IN_FLIGHT;
+        let mut id: gl::types::GLuint = 0;
+        let mut mapped = ptr::null_mut();
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(target, id);
+            if has_buffer_storage() {
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+                gl::BufferStorage(target, size as gl::types::GLsizeiptr, ptr::null(), flags);
+                mapped = gl::MapBufferRange(target, 0, size as gl::types::GLsizeiptr, flags) as *mut u8;
+            } else {
+                gl::BufferData(target, size as gl::types::GLsizeiptr, ptr::null(), gl::STREAM_DRAW);
+            }
+            gl::BindBuffer(target, 0);
+        }
+        StreamBuffer {
+            id,
+            target,
+            region_size: frame_size,
+            region: 0,
+            head: 0,
+            fences: [ptr::null(); FRAMES_IN_FLIGHT],
+            mapped,
+        }
+    }
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+    pub fn is_persistent(&self) -> bool {
+        !self.mapped.is_null()
+    }
+    pub fn frame_capacity(&self) -> usize {
+        self.region_size
+    }
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, self.id);
+        }
+    }
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, 0);
+        }
+    }
+    // byte offset into the buffer where data landed, None once this frame's region is full
+    pub fn write(&mut self, data: &[u8], alignment: usize) -> Option<usize> {
+        // aligned in the whole buffer, so offset / stride is a vertex index
+        let alignment = alignment.max(1);
+        let base = self.region * self.region_size;
+        let offset = (base + self.head).div_ceil(alignment) * alignment;
+        let start = offset - base;
+        if start + data.len() > self.region_size {
+            return None;
+        }
+        unsafe {
+            if self.is_persistent() {
+                ptr::copy_nonoverlapping(data.as_ptr(), self.mapped.add(offset), data.len());
+            } else if !data.is_empty() {
+                self.bind();
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_UNSYNCHRONIZED_BIT | gl::MAP_INVALIDATE_RANGE_BIT;
+                let target = gl::MapBufferRange(self.target, offset as gl::types::GLintptr, data.len() as gl::types::GLsizeiptr, flags) as *mut u8;
+                ptr::copy_nonoverlapping(data.as_ptr(), target, data.len());
+                gl::UnmapBuffer(self.target);
+                self.unbind();
+            }
+        }
+        self.head = start + data.len();
+        Some(offset)
+    }
+    // packs with the layout, returns the first vertex for glDrawArrays / base vertex against a vao from bind_layout
+    pub fn write_vertices(&mut self, vertices: &[f32], layout: &VertexLayout) -> Option<usize> {
+        let stride = layout.get_stride();
+        self.write(&layout.pack(vertices), stride)
+            .map(|offset| offset / stride)
+    }
+    // points the vao's attributes at the start of this buffer
+    pub fn bind_layout(&self, vertex_array: &VertexArray, layout: &VertexLayout) {
+        vertex_array.bind();
+        self.bind();
+        layout.set_attrib_pointers();
+        vertex_array.unbind();
+        self.unbind();
+    }
+    // after the frame's draws are issued
+    pub fn end_frame(&mut self) {
+        unsafe {
+            self.fences[self.region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.region = (self.region + 1) % FRAMES_IN_FLIGHT;
+            self.head = 0;
+
+            let fence = self.fences[self.region];
+            if !fence.is_null() {
+                gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT);
+                gl::DeleteSync(fence);
+                self.fences[self.region] = ptr::null();
+            }
+        }
+    }
+}
+
+impl Drop for StreamBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            for fence in self.fences.iter() {
+                if !fence.is_null() {
+                    gl::DeleteSync(*fence);
+                }
+            }
+            if self.is_persistent() {
+                self.bind();
+                gl::UnmapBuffer(self.target);
+                self.unbind();
+            }
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::cell::Cell;
+
+use serde::Deserialize;
 
 #[derive(Debug)]
 pub struct Texture {
@@ -7,7 +10,12 @@ pub struct Texture {
     pub dimensions: (u32, u32),
     pub texture_type: TextureType,
     pub textureID: gl::types::GLuint,
-    pub is_loaded: bool,
+    // set by the first load_memory, later calls are no-ops
+    is_loaded: Cell<bool>,
+    pub sampler: SamplerDesc,
+    pub mip_generation: MipGeneration,
+    // levels 1.. when generated on the cpu, level 0 lives in texture_type
+    pub mip_levels: Vec<MipLevel>,
 }
 
 impl Texture {
@@ -24,7 +32,10 @@ impl Texture {
             dimensions,
             texture_type: TextureType::FramebufferAttachment(attachment_type),
             textureID: textureID,
-            is_loaded: false,
+            is_loaded: Cell::new(false),
+            sampler: SamplerDesc::linear_repeat(),
+            mip_generation: MipGeneration::None,
+            mip_levels: Vec::new(),
         }
     }
     pub fn create_with_data(internal_format: gl::types::GLenum, format: gl::types::GLenum, data_type: gl::types::GLenum, dimensions: (u32, u32), bytes: Vec<u8>) -> Self {
@@ -40,12 +51,82 @@ impl Texture {
             dimensions,
             texture_type: TextureType::Data(bytes),
             textureID: textureID,
-            is_loaded: false,
+            is_loaded: Cell::new(false),
+            sampler: SamplerDesc::linear_repeat(),
+            mip_generation: MipGeneration::None,
+            mip_levels: Vec::new(),
+        }
+    }
+    // faces in GL order: +x, -x, +y, -y, +z, -z
+    pub fn create_cubemap_with_data(internal_format: gl::types::GLenum, format: gl::types::GLenum, data_type: gl::types::GLenum, face_size: u32, faces: Vec<Vec<u8>>) -> Self {
+        let mut texture_id: gl::types::GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture_id);
+        }
+        Texture {
+            internal_format,
+            format,
+            data_type,
+            dimensions: (face_size, face_size),
+            texture_type: TextureType::Cubemap(faces),
+            textureID: texture_id,
+            is_loaded: Cell::new(false),
+            sampler: SamplerDesc::linear_clamp(),
+            mip_generation: MipGeneration::None,
+            mip_levels: Vec::new(),
+        }
+    }
+    pub fn target(&self) -> gl::types::GLenum {
+        match self.texture_type {
+            TextureType::Cubemap(_) => gl::TEXTURE_CUBE_MAP,
+            _ => gl::TEXTURE_2D,
         }
     }
 
+    pub fn is_resident(&self) -> bool {
+        self.is_loaded.get()
+    }
+    // pixel data kept around for uploads
+    pub fn cpu_bytes(&self) -> usize {
+        let base = match &self.texture_type {
+            TextureType::Data(bytes) => bytes.len(),
+            TextureType::Cubemap(faces) => faces.iter().map(|face| face.len()).sum(),
+            TextureType::FramebufferAttachment(_) => 0,
+        };
+        base + self.mip_levels.iter().map(|level| level.bytes.len()).sum::<usize>()
+    }
+    // estimate from dimensions and internal format, drivers may pad, 0 while not resident
+    pub fn gpu_bytes(&self) -> usize {
+        if !self.is_resident() {
+            return 0;
+        }
+        let pixel_size = internal_format_size(self.internal_format);
+        let level_size = |dimensions: (u32, u32)| dimensions.0 as usize * dimensions.1 as usize * pixel_size;
+        let mut bytes = level_size(self.dimensions);
+        match self.mip_generation {
+            MipGeneration::None => (),
+            MipGeneration::Cpu => bytes += self.mip_levels.iter().map(|level| level_size(level.dimensions)).sum::<usize>(),
+            MipGeneration::Gpu => {
+                let mut dimensions = self.dimensions;
+                while dimensions.0 > 1 || dimensions.1 > 1 {
+                    dimensions = ((dimensions.0 / 2).max(1), (dimensions.1 / 2).max(1));
+                    bytes += level_size(dimensions);
+                }
+            }
+        }
+        match self.texture_type {
+            TextureType::Cubemap(_) => bytes * 6,
+            _ => bytes,
+        }
+    }
+    // uploads once, does nothing while resident
     // TODO: make color format flexible
     pub fn load_memory(&self) {
+        if self.is_loaded.get() {
+            return;
+        }
+        self.is_loaded.set(true);
         match &self.texture_type {
             TextureType::FramebufferAttachment(_) => {
                 self.bind();
@@ -57,18 +138,100 @@ impl Texture {
             TextureType::Data(bytes) => {
                 self.bind();
                 unsafe {
+                    // rows of RGB8 and single channel images aren't 4 byte aligned
+                    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
                     gl::TexImage2D(gl::TEXTURE_2D, 0, self.internal_format as i32, self.dimensions.0 as gl::types::GLint, self.dimensions.1 as gl::types::GLint, 0, self.format, self.data_type, bytes.as_ptr() as *const gl::types::GLvoid);
+                    match self.mip_generation {
+                        MipGeneration::None => (),
+                        MipGeneration::Gpu => gl::GenerateMipmap(gl::TEXTURE_2D),
+                        MipGeneration::Cpu => {
+                            for (i, level) in self.mip_levels.iter().enumerate() {
+                                gl::TexImage2D(gl::TEXTURE_2D, (i + 1) as gl::types::GLint, self.internal_format as i32, level.dimensions.0 as gl::types::GLint, level.dimensions.1 as gl::types::GLint, 0, self.format, self.data_type, level.bytes.as_ptr() as *const gl::types::GLvoid);
+                            }
+                            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, self.mip_levels.len() as gl::types::GLint);
+                        }
+                    }
+                }
+                self.unbind();
+            },
+            TextureType::Cubemap(faces) => {
+                self.bind();
+                unsafe {
+                    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+                    for (i, face) in faces.iter().enumerate() {
+                        gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as gl::types::GLenum, 0, self.internal_format as i32, self.dimensions.0 as gl::types::GLint, self.dimensions.1 as gl::types::GLint, 0, self.format, self.data_type, face.as_ptr() as *const gl::types::GLvoid);
+                    }
+                    if self.mip_generation != MipGeneration::None {
+                        gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+                    }
                 }
                 self.unbind();
             }
         }
     }
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        self.bind();
+        unsafe {
+            gl::TexParameteri(self.target(), gl::TEXTURE_MIN_FILTER, sampler.min_filter_enum() as gl::types::GLint);
+            gl::TexParameteri(self.target(), gl::TEXTURE_MAG_FILTER, sampler.mag_filter.as_gl_enum() as gl::types::GLint);
+            gl::TexParameteri(self.target(), gl::TEXTURE_WRAP_S, sampler.wrap_s.as_gl_enum() as gl::types::GLint);
+            gl::TexParameteri(self.target(), gl::TEXTURE_WRAP_T, sampler.wrap_t.as_gl_enum() as gl::types::GLint);
+            gl::TexParameteri(self.target(), gl::TEXTURE_WRAP_R, sampler.wrap_r.as_gl_enum() as gl::types::GLint);
+            gl::TexParameterfv(self.target(), gl::TEXTURE_BORDER_COLOR, sampler.border_color.as_ptr());
+            let anisotropy = sampler.anisotropy.min(max_anisotropy());
+            if anisotropy > 1f32 {
+                gl::TexParameterf(self.target(), TEXTURE_MAX_ANISOTROPY, anisotropy);
+            }
+        }
+        self.unbind();
+    }
+    // Cpu builds the whole chain now so load_memory only uploads, Gpu defers to glGenerateMipmap on upload
+    pub fn generate_mipmaps(&mut self, mip_generation: MipGeneration) {
+        // upload again with the new chain
+        self.is_loaded.set(false);
+        self.mip_levels.clear();
+        self.mip_generation = mip_generation;
+        if mip_generation != MipGeneration::Cpu {
+            return;
+        }
+        // no cpu path for cube faces
+        if let TextureType::Cubemap(_) = self.texture_type {
+            self.mip_generation = MipGeneration::Gpu;
+            return;
+        }
+        let components = match format_components(self.format) {
+            Some(components) => components,
+            None => {
+                // unknown layout, let the driver do it
+                self.mip_generation = MipGeneration::Gpu;
+                return;
+            }
+        };
+        if let TextureType::Data(bytes) = &self.texture_type {
+            let mut levels: Vec<MipLevel> = Vec::new();
+            let mut dimensions = self.dimensions;
+            while dimensions.0 > 1 || dimensions.1 > 1 {
+                let previous = match levels.last() {
+                    Some(level) => &level.bytes[..],
+                    None => &bytes[..],
+                };
+                let (next_dimensions, next_bytes) = downsample(previous, dimensions, components, self.data_type);
+                levels.push(MipLevel {
+                    dimensions: next_dimensions,
+                    bytes: next_bytes,
+                });
+                dimensions = next_dimensions;
+            }
+            self.mip_levels = levels;
+        }
+    }
     pub fn set_parameters(&self, parameters: Vec<(gl::types::GLenum, gl::types::GLenum)>) {
         self.bind();
         unsafe {
             for i in 0..parameters.len() {
                 let para = parameters.get(i).unwrap();
-                gl::TexParameteri(gl::TEXTURE_2D, para.0, para.1 as gl::types::GLint);
+                gl::TexParameteri(self.target(), para.0, para.1 as gl::types::GLint);
             }
         }
         self.unbind();
@@ -76,12 +239,18 @@ impl Texture {
     pub fn bind(&self) {
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, self.textureID);
+            gl::BindTexture(self.target(), self.textureID);
+        }
+    }
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(self.target(), self.textureID);
         }
     }
     pub fn unbind(&self) {
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindTexture(self.target(), 0);
         }
     }
 }
@@ -100,8 +269,305 @@ impl Drop for Texture {
     }
 }
 
+// decoded pixels without a GL texture, so loaders can build it on any thread
+#[derive(Debug)]
+pub struct ImageData {
+    pub internal_format: gl::types::GLenum,
+    pub format: gl::types::GLenum,
+    pub data_type: gl::types::GLenum,
+    pub dimensions: (u32, u32),
+    pub bytes: Vec<u8>,
+    // applied with set_parameters once the texture exists
+    pub swizzle: Vec<(gl::types::GLenum, gl::types::GLenum)>,
+}
+
+impl ImageData {
+    // GL thread only
+    pub fn into_texture(self) -> Texture {
+        let texture = Texture::create_with_data(self.internal_format, self.format, self.data_type, self.dimensions, self.bytes);
+        if !self.swizzle.is_empty() {
+            texture.set_parameters(self.swizzle);
+        }
+        texture
+    }
+}
+
 #[derive(Debug)]
 pub enum TextureType {
     Data(Vec<u8>),
     FramebufferAttachment(gl::types::GLenum),
+    Cubemap(Vec<Vec<u8>>),
+}
+
+#[derive(Debug)]
+pub struct MipLevel {
+    pub dimensions: (u32, u32),
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MipGeneration {
+    None,
+    Gpu,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+impl Filter {
+    pub fn as_gl_enum(&self) -> gl::types::GLenum {
+        match self {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+// how samples are picked between mip levels, None samples level 0 only
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MipMode {
+    None,
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl Wrap {
+    pub fn as_gl_enum(&self) -> gl::types::GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mip_mode: MipMode,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    // 1.0 is off, clamped to what the driver supports
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+impl SamplerDesc {
+    pub fn new(filter: Filter, mip_mode: MipMode, wrap: Wrap) -> Self {
+        SamplerDesc {
+            min_filter: filter,
+            mag_filter: filter,
+            mip_mode,
+            wrap_s: wrap,
+            wrap_t: wrap,
+            wrap_r: wrap,
+            anisotropy: 1f32,
+            border_color: [0f32, 0f32, 0f32, 0f32],
+        }
+    }
+    pub fn linear_repeat() -> Self {
+        SamplerDesc::new(Filter::Linear, MipMode::None, Wrap::Repeat)
+    }
+    pub fn linear_clamp() -> Self {
+        SamplerDesc::new(Filter::Linear, MipMode::None, Wrap::ClampToEdge)
+    }
+    pub fn nearest_clamp() -> Self {
+        SamplerDesc::new(Filter::Nearest, MipMode::None, Wrap::ClampToEdge)
+    }
+    pub fn trilinear_repeat(anisotropy: f32) -> Self {
+        let mut sampler = SamplerDesc::new(Filter::Linear, MipMode::Linear, Wrap::Repeat);
+        sampler.anisotropy = anisotropy;
+        sampler
+    }
+    pub fn min_filter_enum(&self) -> gl::types::GLenum {
+        match (self.min_filter, self.mip_mode) {
+            (Filter::Nearest, MipMode::None) => gl::NEAREST,
+            (Filter::Linear, MipMode::None) => gl::LINEAR,
+            (Filter::Nearest, MipMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+// what loaders ask for on color textures, clamped per device in set_sampler
+pub const DEFAULT_ANISOTROPY: f32 = 8f32;
+
+// EXT_texture_filter_anisotropic, core in 4.6 but not in the generated bindings
+const TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FF;
+
+// 1.0 when the extension is missing, the query then leaves the value untouched
+pub fn max_anisotropy() -> f32 {
+    let mut max = 1f32;
+    unsafe {
+        gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+        // swallow the INVALID_ENUM from drivers without the extension
+        gl::GetError();
+    }
+    max.max(1f32)
+}
+
+// bytes per pixel, unsized formats count as what drivers usually pick
+fn internal_format_size(internal_format: gl::types::GLenum) -> usize {
+    match internal_format {
+        gl::R8 | gl::RED => 1,
+        gl::RG8 | gl::R16 | gl::R16F => 2,
+        gl::RGB8 | gl::SRGB8 | gl::RGB => 3,
+        gl::RGBA8 | gl::SRGB8_ALPHA8 | gl::RGBA | gl::RG16 | gl::RG16F | gl::R32F => 4,
+        gl::DEPTH_COMPONENT | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32F | gl::DEPTH24_STENCIL8 => 4,
+        gl::RGB16 | gl::RGB16F => 6,
+        gl::RGBA16 | gl::RGBA16F | gl::RG32F => 8,
+        gl::RGB32F => 12,
+        gl::RGBA32F => 16,
+        _ => 4,
+    }
+}
+
+fn format_components(format: gl::types::GLenum) -> Option<usize> {
+    match format {
+        gl::RED => Some(1),
+        gl::RG => Some(2),
+        gl::RGB | gl::BGR => Some(3),
+        gl::RGBA | gl::BGRA => Some(4),
+        _ => None,
+    }
+}
+
+fn read_component(bytes: &[u8], index: usize, data_type: gl::types::GLenum) -> f32 {
+    match data_type {
+        gl::UNSIGNED_SHORT => u16::from_ne_bytes([bytes[index * 2], bytes[index * 2 + 1]]) as f32,
+        gl::FLOAT => f32::from_ne_bytes([bytes[index * 4], bytes[index * 4 + 1], bytes[index * 4 + 2], bytes[index * 4 + 3]]),
+        _ => bytes[index] as f32,
+    }
+}
+
+fn write_component(bytes: &mut Vec<u8>, value: f32, data_type: gl::types::GLenum) {
+    match data_type {
+        gl::UNSIGNED_SHORT => bytes.extend_from_slice(&(value.round() as u16).to_ne_bytes()),
+        gl::FLOAT => bytes.extend_from_slice(&value.to_ne_bytes()),
+        _ => bytes.push(value.round() as u8),
+    }
+}
+
+// 2x2 box filter, on odd sizes the last texel of a row or column is folded into the last output texel as a third tap
+fn downsample(bytes: &[u8], dimensions: (u32, u32), components: usize, data_type: gl::types::GLenum) -> ((u32, u32), Vec<u8>) {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let component_size = match data_type {
+        gl::UNSIGNED_SHORT => 2,
+        gl::FLOAT => 4,
+        _ => 1,
+    };
+    // source texels covered by output texel i
+    let span = |i: usize, new_size: usize, size: usize| {
+        let end = if i + 1 == new_size { size } else { 2 * i + 2 };
+        2 * i..end
+    };
+    let mut out: Vec<u8> = Vec::with_capacity(new_width * new_height * components * component_size);
+    for y in 0..new_height {
+        let rows = span(y, new_height, height);
+        for x in 0..new_width {
+            let columns = span(x, new_width, width);
+            let taps = (rows.len() * columns.len()) as f32;
+            for c in 0..components {
+                let mut sum = 0f32;
+                for source_y in rows.clone() {
+                    for source_x in columns.clone() {
+                        sum += read_component(bytes, (source_y * width + source_x) * components + c, data_type);
+                    }
+                }
+                write_component(&mut out, sum / taps, data_type);
+            }
+        }
+    }
+    ((new_width as u32, new_height as u32), out)
+}
+
+// GL cube face direction for face texel coordinates u, v in [-1, 1]
+fn cube_face_direction(face: usize, u: f32, v: f32) -> (f32, f32, f32) {
+    match face {
+        0 => (1f32, -v, -u),
+        1 => (-1f32, -v, u),
+        2 => (u, 1f32, v),
+        3 => (u, -1f32, -v),
+        4 => (u, -v, 1f32),
+        _ => (-u, -v, -1f32),
+    }
+}
+
+// bilinear lookup into an equirectangular RGB float image, wraps around horizontally
+fn sample_equirect(dimensions: (u32, u32), pixels: &[f32], x: f32, y: f32) -> [f32; 3] {
+    let (width, height) = (dimensions.0 as i64, dimensions.1 as i64);
+    let x = x - 0.5;
+    let y = (y - 0.5).max(0f32).min((height - 1) as f32);
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let texel = |tx: i64, ty: i64| {
+        let tx = tx.rem_euclid(width);
+        let ty = ty.max(0).min(height - 1);
+        let i = ((ty * width + tx) * 3) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+    let (a, b, c, d) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+    let mut out = [0f32; 3];
+    for k in 0..3 {
+        let top = a[k] + (b[k] - a[k]) * fx;
+        let bottom = c[k] + (d[k] - c[k]) * fx;
+        out[k] = top + (bottom - top) * fy;
+    }
+    out
+}
+
+// y is up, the center column of the equirect image looks down +x
+pub fn equirect_to_cube_faces(dimensions: (u32, u32), pixels: &[f32], face_size: u32) -> Vec<Vec<f32>> {
+    let mut faces = Vec::with_capacity(6);
+    for face in 0..6 {
+        let mut face_pixels: Vec<f32> = Vec::with_capacity((face_size * face_size * 3) as usize);
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let u = 2f32 * (x as f32 + 0.5) / face_size as f32 - 1f32;
+                let v = 2f32 * (y as f32 + 0.5) / face_size as f32 - 1f32;
+                let (dx, dy, dz) = cube_face_direction(face, u, v);
+                let length = (dx * dx + dy * dy + dz * dz).sqrt();
+                let (dx, dy, dz) = (dx / length, dy / length, dz / length);
+
+                let phi = dz.atan2(dx);
+                let theta = dy.clamp(-1f32, 1f32).acos();
+                let ex = (0.5 + phi / (2f32 * std::f32::consts::PI)) * dimensions.0 as f32;
+                let ey = (theta / std::f32::consts::PI) * dimensions.1 as f32;
+                face_pixels.extend_from_slice(&sample_equirect(dimensions, pixels, ex, ey));
+            }
+        }
+        faces.push(face_pixels);
+    }
+    faces
+}
+
+pub fn f32_to_bytes(values: &[f32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 4);
+    for value in values.iter() {
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    bytes
 }
\ No newline at end of file
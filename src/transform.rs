@@ -66,6 +66,42 @@ impl Transform {
             scale: Vector3f::new(1f32, 1f32, 1f32),
         }
     }
+    // decompose a translation * rotation * scale matrix, shear from non-uniform scaled parents is lost
+    pub fn from_mat(m: &Mat4f) -> Self {
+        let translation = Vector3f::new(m.get(0, 3), m.get(1, 3), m.get(2, 3));
+        let mut columns = [
+            Vector3f::new(m.get(0, 0), m.get(1, 0), m.get(2, 0)),
+            Vector3f::new(m.get(0, 1), m.get(1, 1), m.get(2, 1)),
+            Vector3f::new(m.get(0, 2), m.get(1, 2), m.get(2, 2)),
+        ];
+        let scale = Vector3f::new(columns[0].mag(), columns[1].mag(), columns[2].mag());
+        for column in columns.iter_mut() {
+            if !column.is_zero() {
+                column.normalize();
+            }
+        }
+        // model_rot is z * y * x, so r20 = -sin(y)
+        let r20 = columns[0].z;
+        let rotation = if r20.abs() < 0.99999f32 {
+            Vector3f::new(
+                columns[1].z.atan2(columns[2].z),
+                (-r20).asin(),
+                columns[0].y.atan2(columns[0].x),
+            )
+        } else {
+            // gimbal lock, fold x rotation into z
+            Vector3f::new(
+                0f32,
+                -r20.signum() * std::f32::consts::FRAC_PI_2,
+                (-columns[1].x).atan2(columns[1].y),
+            )
+        };
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
     pub fn model_rot(&self) -> Mat4f {
         let z_rot = Mat4f::rotation_z(self.rotation.z);
         let y_rot = Mat4f::rotation_y(self.rotation.y);
@@ -2,32 +2,175 @@
 Partially derived from
 https://github.com/Nercury/rust-and-opengl-lessons
 */
-#[derive(Debug)]
+use std::cell::Cell;
+
+use crate::mesh::MeshData;
+
+/*
+mesh data always holds one f32 per component, integer attributes included
+each attribute decides how it's stored in the vertex buffer, packing happens on upload
+packed variants read the same floats as their f32 counterparts, so a layout can be swapped for VertexLayout::packed()
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AttributeType {
     Position2D,
     Position3D,
     Color,
     Normal3D,
     ST,
+    // xyz + handedness in w
+    Tangent4D,
+    ColorRGBA,
+    // second uv set
+    ST2,
+    // four joint indices, integer attribute (ivec4/uvec4 in the shader)
+    BoneIndices,
+    BoneWeights,
+    // per instance: model matrix (column major, takes 4 locations) and an integer index e.g. into a material table
+    Mat4,
+    Index,
+
+    // packed
+    ColorRGBA8,
+    Normal3DPacked,
+    Tangent4DPacked,
+    STHalf,
+    ST2Half,
+    BoneWeights8,
 }
 
-fn get_attribute_type_size(v: &AttributeType) -> usize {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentType {
+    Float,
+    HalfFloat,
+    // 0..1
+    UnsignedByteNorm,
+    // -1..1
+    ShortNorm,
+    // integer attribute
+    UnsignedByte,
+}
+
+impl ComponentType {
+    pub fn size(&self) -> usize {
+        match self {
+            ComponentType::Float => 4,
+            ComponentType::HalfFloat | ComponentType::ShortNorm => 2,
+            ComponentType::UnsignedByteNorm | ComponentType::UnsignedByte => 1,
+        }
+    }
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            ComponentType::Float => gl::FLOAT,
+            ComponentType::HalfFloat => gl::HALF_FLOAT,
+            ComponentType::UnsignedByteNorm | ComponentType::UnsignedByte => gl::UNSIGNED_BYTE,
+            ComponentType::ShortNorm => gl::SHORT,
+        }
+    }
+    pub fn is_normalized(&self) -> bool {
+        matches!(self, ComponentType::UnsignedByteNorm | ComponentType::ShortNorm)
+    }
+    // bound with glVertexAttribIPointer
+    pub fn is_integer(&self) -> bool {
+        *self == ComponentType::UnsignedByte
+    }
+    fn write(&self, value: f32, out: &mut Vec<u8>) {
+        match self {
+            ComponentType::Float => out.extend_from_slice(&value.to_ne_bytes()),
+            ComponentType::HalfFloat => out.extend_from_slice(&f32_to_f16(value).to_ne_bytes()),
+            ComponentType::UnsignedByteNorm => out.push((value.clamp(0f32, 1f32) * 255f32).round() as u8),
+            ComponentType::ShortNorm => out.extend_from_slice(&((value.clamp(-1f32, 1f32) * 32767f32).round() as i16).to_ne_bytes()),
+            ComponentType::UnsignedByte => out.push(value.clamp(0f32, 255f32).round() as u8),
+        }
+    }
+}
+
+// (components, component type, padding components so every attribute stays 4 byte aligned)
+fn get_attribute_type_format(v: &AttributeType) -> (usize, ComponentType, usize) {
     match v {
-        AttributeType::Position2D => 2 * std::mem::size_of::<f32>(),
-        AttributeType::Position3D => 3 * std::mem::size_of::<f32>(),
-        AttributeType::Color => 3 * std::mem::size_of::<f32>(),
-        AttributeType::Normal3D => 3 * std::mem::size_of::<f32>(),
-        AttributeType::ST => 2 * std::mem::size_of::<f32>(),
+        AttributeType::Position2D => (2, ComponentType::Float, 0),
+        AttributeType::Position3D => (3, ComponentType::Float, 0),
+        AttributeType::Color => (3, ComponentType::Float, 0),
+        AttributeType::Normal3D => (3, ComponentType::Float, 0),
+        AttributeType::ST => (2, ComponentType::Float, 0),
+        AttributeType::Tangent4D => (4, ComponentType::Float, 0),
+        AttributeType::ColorRGBA => (4, ComponentType::Float, 0),
+        AttributeType::ST2 => (2, ComponentType::Float, 0),
+        AttributeType::BoneIndices => (4, ComponentType::UnsignedByte, 0),
+        AttributeType::BoneWeights => (4, ComponentType::Float, 0),
+        AttributeType::Mat4 => (16, ComponentType::Float, 0),
+        AttributeType::Index => (1, ComponentType::UnsignedByte, 3),
+        AttributeType::ColorRGBA8 => (4, ComponentType::UnsignedByteNorm, 0),
+        AttributeType::Normal3DPacked => (3, ComponentType::ShortNorm, 1),
+        AttributeType::Tangent4DPacked => (4, ComponentType::ShortNorm, 0),
+        AttributeType::STHalf => (2, ComponentType::HalfFloat, 0),
+        AttributeType::ST2Half => (2, ComponentType::HalfFloat, 0),
+        AttributeType::BoneWeights8 => (4, ComponentType::UnsignedByteNorm, 0),
     }
 }
+// bytes in the vertex buffer
+fn get_attribute_type_size(v: &AttributeType) -> usize {
+    let (components, component_type, padding) = get_attribute_type_format(v);
+    (components + padding) * component_type.size()
+}
 fn get_attribute_type_num_components(v: &AttributeType) -> usize {
-    match v {
-        AttributeType::Position2D => 2,
-        AttributeType::Position3D => 3,
-        AttributeType::Color => 3,
-        AttributeType::Normal3D => 3,
-        AttributeType::ST => 2,
+    get_attribute_type_format(v).0
+}
+
+impl AttributeType {
+    pub fn num_components(&self) -> usize {
+        get_attribute_type_num_components(self)
+    }
+    pub fn component_type(&self) -> ComponentType {
+        get_attribute_type_format(self).1
+    }
+    // attribute locations taken, a location holds at most 4 components
+    pub fn num_locations(&self) -> usize {
+        self.num_components().div_ceil(4)
+    }
+    // the smaller variant reading the same data, if there is one
+    pub fn packed(&self) -> AttributeType {
+        match self {
+            AttributeType::Normal3D => AttributeType::Normal3DPacked,
+            AttributeType::Tangent4D => AttributeType::Tangent4DPacked,
+            AttributeType::ColorRGBA => AttributeType::ColorRGBA8,
+            AttributeType::ST => AttributeType::STHalf,
+            AttributeType::ST2 => AttributeType::ST2Half,
+            AttributeType::BoneWeights => AttributeType::BoneWeights8,
+            other => *other,
+        }
+    }
+}
+
+// round to nearest, overflow goes to infinity
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+    if exponent == 0xff {
+        // inf, nan keeps a mantissa bit
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
     }
+    if exponent <= 0 {
+        // subnormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x800000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+    // a rounding carry moves into the exponent, which is what we want
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
 }
 
 #[derive(Debug)]
@@ -41,6 +184,9 @@ impl VertexLayout {
             types: vl,
         }
     }
+    pub fn types(&self) -> &[AttributeType] {
+        &self.types
+    }
     pub fn get_num_components(&self) -> usize {
         let mut components = 0;
         for vertex_type in self.types.iter() {
@@ -67,6 +213,17 @@ impl VertexLayout {
         }
         offset
     }
+    // in floats of the cpu side vertex, for the first of the given types the layout has
+    pub fn component_offset(&self, types: &[AttributeType]) -> Option<usize> {
+        let mut offset = 0;
+        for vertex_type in self.types.iter() {
+            if types.contains(vertex_type) {
+                return Some(offset);
+            }
+            offset += get_attribute_type_num_components(vertex_type);
+        }
+        None
+    }
     pub fn get_width(&self) -> usize {
         let mut width = 0;
         for vertex_type in self.types.iter() {
@@ -74,59 +231,233 @@ impl VertexLayout {
         }
         width
     }
+    pub fn num_locations(&self) -> usize {
+        self.types.iter().map(|t| t.num_locations()).sum()
+    }
+    // (location, components, integer) for every location set_attrib_pointers_at(first_location, _) fills
+    pub fn locations(&self, first_location: usize) -> Vec<(usize, usize, bool)> {
+        let mut locations = Vec::new();
+        let mut location = first_location;
+        for vertex_type in self.types.iter() {
+            let mut remaining = vertex_type.num_components();
+            while remaining > 0 {
+                let components = remaining.min(4);
+                locations.push((location, components, vertex_type.component_type().is_integer()));
+                remaining -= components;
+                location += 1;
+            }
+        }
+        locations
+    }
+    // for the vao and array buffer currently bound, attributes in order from location 0
+    pub fn set_attrib_pointers(&self) {
+        self.set_attrib_pointers_at(0, 0);
+    }
+    // divisor 0 is per vertex, 1 per instance, returns the next free location
+    pub fn set_attrib_pointers_at(&self, first_location: usize, divisor: u32) -> usize {
+        let stride = self.get_stride() as gl::types::GLint;
+        let mut location = first_location;
+        for i in 0..self.types.len() {
+            let vertex_type = self.types.get(i).unwrap();
+            let component_type = vertex_type.component_type();
+            let mut remaining = get_attribute_type_num_components(vertex_type);
+            let mut offset = self.get_offset(i);
+            // matrices go in as consecutive columns
+            while remaining > 0 {
+                let num_components = remaining.min(4);
+                let index = location as gl::types::GLuint;
+                unsafe {
+                    gl::EnableVertexAttribArray(index);
+                    if component_type.is_integer() {
+                        gl::VertexAttribIPointer(index, num_components as gl::types::GLint, component_type.gl_type(), stride, offset as *const gl::types::GLvoid);
+                    } else {
+                        gl::VertexAttribPointer(
+                            index,
+                            num_components as gl::types::GLint,
+                            component_type.gl_type(),
+                            if component_type.is_normalized() { gl::TRUE } else { gl::FALSE },
+                            stride,
+                            offset as *const gl::types::GLvoid
+                        );
+                    }
+                    gl::VertexAttribDivisor(index, divisor);
+                }
+                remaining -= num_components;
+                offset += num_components * component_type.size();
+                location += 1;
+            }
+        }
+        location
+    }
+    // undoes set_attrib_pointers_at on the bound vao
+    pub fn disable_attrib_arrays_at(&self, first_location: usize) {
+        for location in first_location..first_location + self.num_locations() {
+            unsafe {
+                gl::VertexAttribDivisor(location as gl::types::GLuint, 0);
+                gl::DisableVertexAttribArray(location as gl::types::GLuint);
+            }
+        }
+    }
+    // same data, smaller buffer
+    pub fn packed(&self) -> VertexLayout {
+        VertexLayout::new(self.types.iter().map(|t| t.packed()).collect())
+    }
+    // interleaved f32 vertices to what the buffer holds
+    pub fn pack(&self, vertices: &[f32]) -> Vec<u8> {
+        let width = self.get_num_components();
+        if width == 0 {
+            return Vec::new();
+        }
+        let num_vertices = vertices.len() / width;
+        let mut bytes = Vec::with_capacity(num_vertices * self.get_stride());
+        for vertex in vertices.chunks_exact(width) {
+            let mut offset = 0;
+            for vertex_type in self.types.iter() {
+                let (components, component_type, padding) = get_attribute_type_format(vertex_type);
+                for value in vertex[offset..offset + components].iter() {
+                    component_type.write(*value, &mut bytes);
+                }
+                for _ in 0..padding {
+                    component_type.write(0f32, &mut bytes);
+                }
+                offset += components;
+            }
+        }
+        bytes
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrimitiveMode {
+    Triangles,
+    TriangleStrip,
+    Lines,
+    LineStrip,
+    Points,
+}
+
+impl PrimitiveMode {
+    pub fn gl_mode(&self) -> gl::types::GLenum {
+        match self {
+            PrimitiveMode::Triangles => gl::TRIANGLES,
+            PrimitiveMode::TriangleStrip => gl::TRIANGLE_STRIP,
+            PrimitiveMode::Lines => gl::LINES,
+            PrimitiveMode::LineStrip => gl::LINE_STRIP,
+            PrimitiveMode::Points => gl::POINTS,
+        }
+    }
+}
+
+// picked on upload from the vertex count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexType {
+    U16,
+    U32,
+}
+
+impl IndexType {
+    pub fn for_vertex_count(num_vertices: usize) -> Self {
+        if num_vertices <= u16::MAX as usize + 1 {
+            IndexType::U16
+        } else {
+            IndexType::U32
+        }
+    }
+    pub fn size(&self) -> usize {
+        match self {
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        }
+    }
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            IndexType::U16 => gl::UNSIGNED_SHORT,
+            IndexType::U32 => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+/*
+GL side of a mesh, created from the cpu data on upload
+without indices the vertices are drawn in order with glDrawArrays, ranges then count vertices instead of indices
+*/
 #[derive(Debug)]
 pub struct Vertex {
-    pub vertex_layout: VertexLayout,
     pub vertex_buffer: VertexBuffer,
     pub index_buffer: IndexBuffer,
     pub vertex_array: VertexArray,
-    pub vertices: Vec<f32>,
-    pub indices: Vec<i32>,
+    pub num_vertices: usize,
+    pub num_indices: usize,
+    pub primitive: PrimitiveMode,
+    // None for non-indexed meshes
+    pub index_type: Option<IndexType>,
+    buffer_bytes: usize,
 }
 
 impl Vertex {
-    pub fn new(vertices: Vec<f32>, indices: Vec<i32>, vertex_layout: VertexLayout) -> Self {
-        let vertex_buffer = VertexBuffer::new();
-        let index_buffer = IndexBuffer::new();
-        let vertex_array = VertexArray::new();
+    pub fn new(data: &MeshData) -> Self {
+        let mut vertex = Vertex {
+            vertex_buffer: VertexBuffer::with_usage(data.usage),
+            index_buffer: IndexBuffer::with_usage(data.usage),
+            vertex_array: VertexArray::new(),
+            num_vertices: 0,
+            num_indices: 0,
+            primitive: data.primitive,
+            index_type: None,
+            buffer_bytes: 0,
+        };
+        vertex.update(data);
+        vertex
+    }
+    /*
+    re-uploads into the same GL objects
+    data that fits the current allocation orphans it and writes with BufferSubData, so a frame still
+    drawing the old contents doesn't stall us, anything bigger reallocates
+    */
+    pub fn update(&mut self, data: &MeshData) {
+        let packed = data.vertex_layout.pack(&data.vertices);
+        let num_vertices = data.vertices.len() / data.vertex_layout.get_num_components();
+        let index_type = if data.indices.is_empty() {
+            None
+        } else {
+            Some(IndexType::for_vertex_count(num_vertices))
+        };
+        let index_bytes = index_type.map_or(Vec::new(), |index_type| pack_indices(&data.indices, index_type));
 
-        Vertex {
-            vertex_layout,
-            vertex_buffer,
-            index_buffer,
-            vertex_array,
-            vertices,
-            indices,
+        self.vertex_buffer.update_data(&packed);
+        if index_type.is_some() {
+            self.index_buffer.update_data(&index_bytes);
         }
+        self.num_vertices = num_vertices;
+        self.num_indices = data.indices.len();
+        self.primitive = data.primitive;
+        self.index_type = index_type;
+        self.buffer_bytes = self.vertex_buffer.capacity() + self.index_buffer.capacity();
+        self.set_attrib_pointers(&data.vertex_layout);
     }
-    pub fn buffer_data(&self) {
-        self.vertex_buffer.buffer_data(&self.vertices);
-        self.index_buffer.buffer_data(&self.indices);
+    // rewrites part of the vertex buffer, offset and data in bytes of packed vertices
+    pub fn update_vertex_range(&self, byte_offset: usize, packed: &[u8]) {
+        self.vertex_buffer.sub_data(byte_offset, packed);
     }
-    pub fn set_attrib_pointers(&self) {
+    fn set_attrib_pointers(&self, vertex_layout: &VertexLayout) {
         self.vertex_buffer.bind();
         self.vertex_array.bind();
-        for i in 0..self.vertex_layout.types.len() {
-            let vertex_type = self.vertex_layout.types.get(i).unwrap();
-            unsafe {
-                gl::EnableVertexAttribArray(i as gl::types::GLuint);
-                gl::VertexAttribPointer(
-                    i as gl::types::GLuint,
-                    get_attribute_type_num_components(&vertex_type) as gl::types::GLint,
-                    gl::FLOAT,
-                    gl::FALSE,
-                    self.vertex_layout.get_stride() as gl::types::GLint,
-                    self.vertex_layout.get_offset(i) as *const gl::types::GLvoid
-                );
-            }
-        }
+        vertex_layout.set_attrib_pointers();
         self.vertex_buffer.unbind();
         self.vertex_array.unbind();
     }
     pub fn get_num_vertices(&self) -> usize {
-        self.vertices.len() / self.vertex_layout.get_num_components()
+        self.num_vertices
+    }
+    // indices, or vertices for non-indexed meshes
+    pub fn get_num_elements(&self) -> usize {
+        match self.index_type {
+            Some(_) => self.num_indices,
+            None => self.num_vertices,
+        }
+    }
+    pub fn gpu_bytes(&self) -> usize {
+        self.buffer_bytes
     }
     pub fn bind(&self) {
         self.vertex_array.bind();
@@ -139,19 +470,66 @@ impl Vertex {
         self.index_buffer.unbind();
     }
     pub fn draw_call(&self) {
+        self.draw_range_call(0, self.get_num_elements());
+    }
+    // first and count are in elements, e.g. one sub-mesh's slice of the index buffer
+    pub fn draw_range_call(&self, first: usize, count: usize) {
+        self.instanced_draw_range_call(first, count, 1);
+    }
+    pub fn instanced_draw_call(&self, num: i32) {
+        self.instanced_draw_range_call(0, self.get_num_elements(), num);
+    }
+    /*
+    per instance attributes from the buffers, starting at FIRST_INSTANCE_LOCATION
+    they're hooked into the mesh's vao only for this draw, meshes are shared and other users don't want them
+    */
+    pub fn draw_instances_call(&self, instances: &[&InstanceBuffer]) {
+        let num = instances.iter().map(|buffer| buffer.len()).min().unwrap_or(0);
         self.vertex_array.bind();
-        self.index_buffer.bind();
-
+        let mut location = FIRST_INSTANCE_LOCATION;
+        for buffer in instances.iter() {
+            buffer.buffer.bind();
+            location = buffer.layout.set_attrib_pointers_at(location, 1);
+        }
+        self.instanced_draw_range_call(0, self.get_num_elements(), num as i32);
+        self.vertex_array.bind();
+        let mut location = FIRST_INSTANCE_LOCATION;
+        for buffer in instances.iter() {
+            buffer.layout.disable_attrib_arrays_at(location);
+            location += buffer.layout.num_locations();
+        }
+        self.vertex_array.unbind();
         unsafe {
-            gl::DrawElements(gl::TRIANGLES, self.indices.len() as gl::types::GLsizei, gl::UNSIGNED_INT, 0 as *const gl::types::GLvoid);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
-    pub fn instanced_draw_call(&self, num: i32) {
+    pub fn instanced_draw_range_call(&self, first: usize, count: usize, num: i32) {
+        let count = count.min(self.get_num_elements().saturating_sub(first));
+        if count == 0 || num <= 0 {
+            return;
+        }
         self.vertex_array.bind();
-        self.index_buffer.bind();
+        let mode = self.primitive.gl_mode();
 
         unsafe {
-            gl::DrawElementsInstanced(gl::TRIANGLES, self.indices.len() as gl::types::GLsizei, gl:: UNSIGNED_INT, 0 as *const gl::types::GLvoid, num);
+            match self.index_type {
+                Some(index_type) => {
+                    self.index_buffer.bind();
+                    let offset = (first * index_type.size()) as *const gl::types::GLvoid;
+                    if num == 1 {
+                        gl::DrawElements(mode, count as gl::types::GLsizei, index_type.gl_type(), offset);
+                    } else {
+                        gl::DrawElementsInstanced(mode, count as gl::types::GLsizei, index_type.gl_type(), offset, num);
+                    }
+                },
+                None => {
+                    if num == 1 {
+                        gl::DrawArrays(mode, first as gl::types::GLint, count as gl::types::GLsizei);
+                    } else {
+                        gl::DrawArraysInstanced(mode, first as gl::types::GLint, count as gl::types::GLsizei, num);
+                    }
+                },
+            }
         }
     }
 }
@@ -162,21 +540,64 @@ impl Drop for Vertex {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BufferUsage {
+    // uploaded once
+    Static,
+    // rewritten now and then
+    Dynamic,
+    // rewritten every frame
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn gl_usage(&self) -> gl::types::GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+// the buffer has to be bound to target
+unsafe fn allocate(target: gl::types::GLenum, size: usize, data: *const gl::types::GLvoid, usage: BufferUsage) {
+    gl::BufferData(target, size as gl::types::GLsizeiptr, data, usage.gl_usage());
+}
+
+unsafe fn write_range(target: gl::types::GLenum, capacity: usize, offset: usize, data: &[u8]) {
+    if offset + data.len() > capacity {
+        panic!("sub_data: {} bytes at {} exceed buffer of {}", data.len(), offset, capacity);
+    }
+    gl::BufferSubData(target, offset as gl::types::GLintptr, data.len() as gl::types::GLsizeiptr, data.as_ptr() as *const gl::types::GLvoid);
+}
+
 #[derive(Debug)]
 pub struct VertexBuffer {
     vbo: gl::types::GLuint,
+    usage: BufferUsage,
+    // bytes allocated
+    capacity: Cell<usize>,
 }
 
 impl VertexBuffer {
     pub fn new() -> Self {
+        VertexBuffer::with_usage(BufferUsage::Static)
+    }
+    pub fn with_usage(usage: BufferUsage) -> Self {
         let mut vbo = 0;
         unsafe {
             gl::GenBuffers(1, &mut vbo);
         }
         VertexBuffer {
             vbo: vbo,
+            usage,
+            capacity: Cell::new(0),
         }
     }
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
     pub fn bind(&self) {
         unsafe {
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
@@ -187,18 +608,44 @@ impl VertexBuffer {
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
-    pub fn buffer_data(&self, data: &Vec<f32>) {
+    // reallocates to exactly data
+    pub fn buffer_data(&self, data: &[u8]) {
+        self.bind();
+        unsafe {
+            allocate(gl::ARRAY_BUFFER, data.len(), data.as_ptr() as *const gl::types::GLvoid, self.usage);
+        }
+        self.capacity.set(data.len());
+        self.unbind();
+    }
+    // allocates without contents
+    pub fn reserve(&self, size: usize) {
         self.bind();
         unsafe {
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (data.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
-                data.as_ptr() as *const gl::types::GLvoid,
-                gl::STATIC_DRAW,
-            );
+            allocate(gl::ARRAY_BUFFER, size, std::ptr::null(), self.usage);
         }
+        self.capacity.set(size);
         self.unbind();
     }
+    // new storage of the same size, the driver keeps the old one alive for draws still in flight
+    pub fn orphan(&self) {
+        self.reserve(self.capacity());
+    }
+    pub fn sub_data(&self, offset: usize, data: &[u8]) {
+        self.bind();
+        unsafe {
+            write_range(gl::ARRAY_BUFFER, self.capacity(), offset, data);
+        }
+        self.unbind();
+    }
+    // in place when it fits, static buffers always reallocate
+    pub fn update_data(&self, data: &[u8]) {
+        if self.usage == BufferUsage::Static || data.len() > self.capacity() || self.capacity() == 0 {
+            self.buffer_data(data);
+        } else {
+            self.orphan();
+            self.sub_data(0, data);
+        }
+    }
 }
 
 impl Drop for VertexBuffer {
@@ -209,13 +656,31 @@ impl Drop for VertexBuffer {
     }
 }
 
+// narrows to u16 when asked, callers check the vertex count
+pub fn pack_indices(indices: &[i32], index_type: IndexType) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(indices.len() * index_type.size());
+    for index in indices.iter() {
+        match index_type {
+            IndexType::U16 => bytes.extend_from_slice(&(*index as u16).to_ne_bytes()),
+            IndexType::U32 => bytes.extend_from_slice(&(*index as u32).to_ne_bytes()),
+        }
+    }
+    bytes
+}
+
+// bind/unbind with a vao bound changes what the vao records
 #[derive(Debug)]
 pub struct IndexBuffer {
     ibo: gl::types::GLuint,
+    usage: BufferUsage,
+    capacity: Cell<usize>,
 }
 
 impl IndexBuffer {
     pub fn new() -> Self {
+        IndexBuffer::with_usage(BufferUsage::Static)
+    }
+    pub fn with_usage(usage: BufferUsage) -> Self {
         let mut ibo = 0;
         unsafe {
             gl::GenBuffers(1, &mut ibo);
@@ -223,8 +688,13 @@ impl IndexBuffer {
 
         IndexBuffer {
             ibo: ibo,
+            usage,
+            capacity: Cell::new(0),
         }
     }
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
     pub fn bind(&self) {
         unsafe {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
@@ -235,18 +705,41 @@ impl IndexBuffer {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
     }
-    pub fn buffer_data(&self, data: &Vec<i32>) {
+    // packed indices, see pack_indices
+    pub fn buffer_data(&self, data: &[u8]) {
+        self.bind();
+        unsafe {
+            allocate(gl::ELEMENT_ARRAY_BUFFER, data.len(), data.as_ptr() as *const gl::types::GLvoid, self.usage);
+        }
+        self.capacity.set(data.len());
+        self.unbind();
+    }
+    pub fn reserve(&self, size: usize) {
         self.bind();
         unsafe {
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (data.len() * std::mem::size_of::<i32>()) as gl::types::GLsizeiptr,
-                data.as_ptr() as *const gl::types::GLvoid,
-                gl::STATIC_DRAW
-            );
+            allocate(gl::ELEMENT_ARRAY_BUFFER, size, std::ptr::null(), self.usage);
         }
+        self.capacity.set(size);
         self.unbind();
     }
+    pub fn orphan(&self) {
+        self.reserve(self.capacity());
+    }
+    pub fn sub_data(&self, offset: usize, data: &[u8]) {
+        self.bind();
+        unsafe {
+            write_range(gl::ELEMENT_ARRAY_BUFFER, self.capacity(), offset, data);
+        }
+        self.unbind();
+    }
+    pub fn update_data(&self, data: &[u8]) {
+        if self.usage == BufferUsage::Static || data.len() > self.capacity() || self.capacity() == 0 {
+            self.buffer_data(data);
+        } else {
+            self.orphan();
+            self.sub_data(0, data);
+        }
+    }
 }
 
 impl Drop for IndexBuffer {
@@ -257,6 +750,44 @@ impl Drop for IndexBuffer {
     }
 }
 
+// mesh attributes have to fit below this, instance attributes start here so shaders can hardcode locations
+pub const FIRST_INSTANCE_LOCATION: usize = 8;
+
+// per instance attributes, one entry of the layout per instance
+#[derive(Debug)]
+pub struct InstanceBuffer {
+    buffer: VertexBuffer,
+    layout: VertexLayout,
+    count: Cell<usize>,
+}
+
+impl InstanceBuffer {
+    pub fn new(layout: VertexLayout) -> Self {
+        InstanceBuffer {
+            buffer: VertexBuffer::with_usage(BufferUsage::Dynamic),
+            layout,
+            count: Cell::new(0),
+        }
+    }
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+    pub fn len(&self) -> usize {
+        self.count.get()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // interleaved floats like MeshData, reuses the GL buffer
+    pub fn set_data(&self, instances: &[f32]) {
+        self.buffer.update_data(&self.layout.pack(instances));
+        self.count.set(instances.len() / self.layout.get_num_components().max(1));
+    }
+    pub fn gpu_bytes(&self) -> usize {
+        self.buffer.capacity()
+    }
+}
+
 #[derive(Debug)]
 pub struct VertexArray {
     vao: gl::types::GLuint,
@@ -0,0 +1,213 @@
+/*
+mesh export, the other direction of loader
+any Mesh can be written out as long as its layout has a position
+only triangles make it into the faces, line and point meshes come out as bare vertices
+*/
+
+use std::fs;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+use crate::mesh::Mesh;
+use crate::vertex::AttributeType;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    NoPosition,
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io(other)
+    }
+}
+
+// per vertex attributes pulled out of the interleaved buffer, missing ones stay None
+struct ExportVertex {
+    position: [f32; 3],
+    normal: Option<[f32; 3]>,
+    st: Option<[f32; 2]>,
+    color: Option<[f32; 3]>,
+}
+
+fn export_vertices(mesh: &Mesh) -> Result<Vec<ExportVertex>, Error> {
+    let layout = &mesh.data().vertex_layout;
+    let width = layout.get_num_components();
+    let has_position = layout.types().iter().any(|t| matches!(t, AttributeType::Position2D | AttributeType::Position3D));
+    if !has_position || width == 0 {
+        return Err(Error::NoPosition);
+    }
+
+    let mut vertices = Vec::with_capacity(mesh.data().vertices.len() / width);
+    for data in mesh.data().vertices.chunks_exact(width) {
+        let mut vertex = ExportVertex {
+            position: [0f32; 3],
+            normal: None,
+            st: None,
+            color: None,
+        };
+        let mut offset = 0;
+        for attribute in layout.types().iter() {
+            let v = &data[offset..offset + attribute.num_components()];
+            match attribute {
+                AttributeType::Position2D => vertex.position = [v[0], v[1], 0f32],
+                AttributeType::Position3D => vertex.position = [v[0], v[1], v[2]],
+                AttributeType::Normal3D | AttributeType::Normal3DPacked => vertex.normal = Some([v[0], v[1], v[2]]),
+                AttributeType::ST | AttributeType::STHalf => vertex.st = Some([v[0], v[1]]),
+                // alpha has nowhere to go
+                AttributeType::Color | AttributeType::ColorRGBA | AttributeType::ColorRGBA8 => vertex.color = Some([v[0], v[1], v[2]]),
+                // neither format has a place for these
+                AttributeType::Tangent4D | AttributeType::Tangent4DPacked
+                | AttributeType::ST2 | AttributeType::ST2Half
+                | AttributeType::BoneIndices | AttributeType::BoneWeights | AttributeType::BoneWeights8
+                | AttributeType::Mat4 | AttributeType::Index => (),
+            }
+            offset += attribute.num_components();
+        }
+        vertices.push(vertex);
+    }
+    Ok(vertices)
+}
+
+fn color_byte(c: f32) -> u8 {
+    (c.clamp(0f32, 1f32) * 255f32).round() as u8
+}
+
+/*
+PLY
+property names follow what Blender and load_ply expect: x y z nx ny nz s t, colors as uchar red green blue
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+pub fn save_ply(mesh: &Mesh, format: PlyFormat, path: &Path) -> Result<(), Error> {
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    write_ply(mesh, format, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn write_ply<W: Write>(mesh: &Mesh, format: PlyFormat, out: &mut W) -> Result<(), Error> {
+    let vertices = export_vertices(mesh)?;
+    let indices = mesh.data().triangle_indices();
+    let triangles = indices.chunks_exact(3);
+    let has_normal = vertices.first().is_some_and(|v| v.normal.is_some());
+    let has_st = vertices.first().is_some_and(|v| v.st.is_some());
+    let has_color = vertices.first().is_some_and(|v| v.color.is_some());
+
+    writeln!(out, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(out, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(out, "format binary_little_endian 1.0")?,
+    }
+    writeln!(out, "element vertex {}", vertices.len())?;
+    writeln!(out, "property float x\nproperty float y\nproperty float z")?;
+    if has_normal {
+        writeln!(out, "property float nx\nproperty float ny\nproperty float nz")?;
+    }
+    if has_st {
+        writeln!(out, "property float s\nproperty float t")?;
+    }
+    if has_color {
+        writeln!(out, "property uchar red\nproperty uchar green\nproperty uchar blue")?;
+    }
+    writeln!(out, "element face {}", triangles.len())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    for vertex in vertices.iter() {
+        let mut floats: Vec<f32> = vertex.position.to_vec();
+        if let Some(normal) = vertex.normal {
+            floats.extend_from_slice(&normal);
+        }
+        if let Some(st) = vertex.st {
+            floats.extend_from_slice(&st);
+        }
+        let color = vertex.color.map(|c| [color_byte(c[0]), color_byte(c[1]), color_byte(c[2])]);
+        match format {
+            PlyFormat::Ascii => {
+                let mut words: Vec<String> = floats.iter().map(|f| f.to_string()).collect();
+                if let Some(color) = color {
+                    words.extend(color.iter().map(|c| c.to_string()));
+                }
+                writeln!(out, "{}", words.join(" "))?;
+            },
+            PlyFormat::BinaryLittleEndian => {
+                for f in floats.iter() {
+                    out.write_all(&f.to_le_bytes())?;
+                }
+                if let Some(color) = color {
+                    out.write_all(&color)?;
+                }
+            },
+        }
+    }
+    for triangle in triangles {
+        match format {
+            PlyFormat::Ascii => writeln!(out, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?,
+            PlyFormat::BinaryLittleEndian => {
+                out.write_all(&[3u8])?;
+                for index in triangle.iter() {
+                    out.write_all(&(*index as u32).to_le_bytes())?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/*
+Wavefront OBJ
+vertices are already unique per corner, so v/vt/vn share one index
+colors go after the position ("v x y z r g b"), which Blender and MeshLab read
+*/
+pub fn save_obj(mesh: &Mesh, path: &Path) -> Result<(), Error> {
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    write_obj(mesh, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn write_obj<W: Write>(mesh: &Mesh, out: &mut W) -> Result<(), Error> {
+    let vertices = export_vertices(mesh)?;
+    let has_normal = vertices.first().is_some_and(|v| v.normal.is_some());
+    let has_st = vertices.first().is_some_and(|v| v.st.is_some());
+
+    for vertex in vertices.iter() {
+        let p = vertex.position;
+        match vertex.color {
+            Some(c) => writeln!(out, "v {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2])?,
+            None => writeln!(out, "v {} {} {}", p[0], p[1], p[2])?,
+        }
+    }
+    for vertex in vertices.iter() {
+        if let Some(st) = vertex.st {
+            writeln!(out, "vt {} {}", st[0], st[1])?;
+        }
+    }
+    for vertex in vertices.iter() {
+        if let Some(n) = vertex.normal {
+            writeln!(out, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+    }
+
+    for triangle in mesh.data().triangle_indices().chunks_exact(3) {
+        let corners: Vec<String> = triangle.iter()
+            .map(|index| {
+                let i = index + 1;
+                match (has_st, has_normal) {
+                    (true, true) => format!("{}/{}/{}", i, i, i),
+                    (true, false) => format!("{}/{}", i, i),
+                    (false, true) => format!("{}//{}", i, i),
+                    (false, false) => format!("{}", i),
+                }
+            })
+            .collect();
+        writeln!(out, "f {}", corners.join(" "))?;
+    }
+    Ok(())
+}